@@ -0,0 +1,156 @@
+/// Lines starting with this separate the queries of a batch, the rest of the line is
+/// the label of the next query
+const MARKER: &str = "#---";
+
+/// A query or update of a batch file
+#[derive(Debug, PartialEq, Eq)]
+pub struct Statement {
+    /// The text after the marker line that starts the statement
+    pub label: Option<String>,
+    /// Line of the file the statement starts on
+    pub line: usize,
+    pub text: String,
+}
+
+impl Statement {
+    /// The label of the marker, or the first line of the statement
+    pub fn title(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        let first_line = self
+            .text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        format!("line {}: {}", self.line, first_line)
+    }
+}
+
+/// Split a batch file into its queries and updates. They are separated by marker lines
+/// (#--- label) and by the semicolons outside of the braces, brackets and parentheses,
+/// like the operations of an update, so the ; of the predicate lists and of
+/// GROUP_CONCAT don't split a query. Strings, IRIs and comments are skipped
+pub fn split_statements(script: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut label = None;
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut line_start = true;
+    let mut chars = script.char_indices().peekable();
+
+    let mut finish = |start: usize, end: usize, label: Option<String>| {
+        let text = &script[start..end];
+        if text.trim().is_empty() {
+            return;
+        }
+        let leading = text.len() - text.trim_start().len();
+        let line = script[..start + leading].matches('\n').count() + 1;
+        statements.push(Statement {
+            label,
+            line,
+            text: text.trim().to_string(),
+        });
+    };
+
+    while let Some((index, c)) = chars.next() {
+        if line_start && c == '#' && script[index..].starts_with(MARKER) {
+            finish(start, index, label.take());
+            let end = script[index..].find('\n').map_or(script.len(), |end| index + end);
+            let text = script[index + MARKER.len()..end].trim_matches(|c: char| c == '-' || c.is_whitespace());
+            label = (!text.is_empty()).then(|| text.to_string());
+            while chars.next_if(|(i, _)| *i < end).is_some() {}
+            start = end;
+            continue;
+        }
+        line_start = c == '\n' || (line_start && c.is_whitespace());
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth = depth.saturating_sub(1),
+            ';' if depth == 0 => {
+                finish(start, index, label.take());
+                start = index + 1;
+            }
+            '#' => {
+                // a comment, up to the end of the line
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '<' => {
+                // an IRI has no spaces, otherwise this is a comparison
+                let rest = &script[index + 1..];
+                if let Some(end) = rest.find(|c: char| c == '>' || c.is_whitespace()) {
+                    if rest[end..].starts_with('>') {
+                        while chars.next_if(|(i, _)| *i <= index + 1 + end).is_some() {}
+                    }
+                }
+            }
+            '"' | '\'' => {
+                let long = script[index..].starts_with(if c == '"' { "\"\"\"" } else { "'''" });
+                if long {
+                    chars.next();
+                    chars.next();
+                }
+                skip_string(script, &mut chars, c, long);
+            }
+            _ => (),
+        }
+    }
+    finish(start, script.len(), label);
+    statements
+}
+
+/// Move past the end of a string, its escaped quotes included
+fn skip_string(
+    script: &str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    quote: char,
+    long: bool,
+) {
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c == quote && !long => return,
+            c if c == quote && script[index..].chars().take(3).all(|c| c == quote) => {
+                chars.next();
+                chars.next();
+                return;
+            }
+            '\n' if !long => return,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_split_on_semicolons() {
+        let script = "PREFIX ex: <https://example.com/ns#>\n\
+            INSERT DATA { ex:a ex:p \"x;y\" ; ex:q 'z' } ;\n\
+            # a comment; not a separator\n\
+            SELECT (GROUP_CONCAT(?o; separator=\";\") AS ?all) { ?s ?p ?o FILTER(?o < 3) }";
+        let statements = split_statements(script);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].text.ends_with("ex:q 'z' }"));
+        assert_eq!(statements[1].line, 3);
+        assert!(statements[1].text.ends_with("FILTER(?o < 3) }"));
+    }
+
+    #[test]
+    fn should_split_on_markers() {
+        let script = "#--- load the people\nINSERT DATA { <a> <b> \"\"\"c\n#--- not a marker\"\"\" }\n\
+            #---\nASK {}\n;\n";
+        let statements = split_statements(script);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].label.as_deref(), Some("load the people"));
+        assert!(statements[0].text.contains("not a marker"));
+        assert_eq!(statements[1].label, None);
+        assert_eq!(statements[1].title(), "line 5: ASK {}");
+    }
+}
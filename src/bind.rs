@@ -0,0 +1,199 @@
+use oxigraph::model::{NamedNode, Term, Variable};
+use oxigraph::sparql::{QueryResults, QuerySolutionIter};
+use spargebra::algebra::GraphPattern;
+use spargebra::Query;
+use std::str::FromStr;
+
+use crate::prefix::Prefix;
+
+/// A `--bind ?name=value` argument. The value is only turned into a term once the
+/// prefixes of the data are known
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub variable: Variable,
+    pub value: String,
+}
+
+/// Value parser for `--bind`: the variable, with or without its ? or $, and the value
+pub fn parse_bind_arg(arg: &str) -> Result<Binding, String> {
+    let (name, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("invalid binding '{arg}', expected ?name=value"))?;
+    let name = name.trim();
+    let name = name.strip_prefix(['?', '$']).unwrap_or(name);
+    let variable = Variable::new(name).map_err(|e| format!("invalid variable '{name}': {e}"))?;
+    Ok(Binding {
+        variable,
+        value: value.trim().to_string(),
+    })
+}
+
+/// Turn the value of a binding into a term. Values are written like in N-Triples
+/// (<https://example.com/a>, "text"@en, "2020"^^<...>, 10, 1.5, true) or as a
+/// prefixed name, also for the datatype of a literal ("2020"^^xsd:gYear)
+pub fn bind_term(value: &str, ns_dict: &Prefix) -> Result<Term, String> {
+    // a literal with a prefixed datatype
+    if let Some((lexical, datatype)) = value.rsplit_once("^^") {
+        if lexical.ends_with('"') && !datatype.starts_with('<') {
+            let datatype = expand(datatype, ns_dict)?;
+            return bind_term(&format!("{lexical}^^<{}>", datatype.as_str()), ns_dict);
+        }
+    }
+    match Term::from_str(value) {
+        Ok(Term::BlankNode(_)) => Err(format!("blank nodes can't be bound: '{value}'")),
+        Ok(term) => Ok(term),
+        Err(_) if value.starts_with(['<', '"']) => Err(format!("invalid IRI or literal '{value}'")),
+        Err(_) if value.contains(':') => expand(value, ns_dict).map(Term::NamedNode),
+        Err(_) => Err(format!(
+            "invalid value '{value}', expected an IRI, a prefixed name or a literal (\"text\")"
+        )),
+    }
+}
+
+/// The IRI of a prefixed name
+fn expand(name: &str, ns_dict: &Prefix) -> Result<NamedNode, String> {
+    let iri = ns_dict
+        .expand_curie(name)
+        .ok_or_else(|| format!("unknown prefix in '{name}'"))?;
+    NamedNode::new(iri).map_err(|e| format!("invalid IRI for '{name}': {e}"))
+}
+
+/// Whether the variable appears in the text of the query, to warn about a binding
+/// that would be ignored
+pub fn uses_variable(query: &str, variable: &Variable) -> bool {
+    let name = variable.as_str();
+    query.match_indices(['?', '$']).any(|(index, _)| {
+        let rest = &query[index + 1..];
+        rest.starts_with(name)
+            && !rest[name.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// Add the bound variables missing from the SELECT clause to its projection and
+/// return them. The values are substituted by oxigraph (SEP-0007), which only
+/// substitutes the variables of the projection, the added ones are hidden again
+/// from the results with [`hide_variables`]
+pub fn project_bindings(query: &mut Query, bindings: &[(Variable, Term)]) -> Vec<Variable> {
+    let Query::Select { pattern, .. } = query else {
+        return Vec::new();
+    };
+    // the projection is under the solution modifiers
+    let mut pattern = pattern;
+    loop {
+        pattern = match pattern {
+            GraphPattern::Slice { inner, .. }
+            | GraphPattern::Distinct { inner }
+            | GraphPattern::Reduced { inner } => inner,
+            GraphPattern::Project { variables, .. } => {
+                let added: Vec<Variable> = bindings
+                    .iter()
+                    .map(|(variable, _)| variable)
+                    .filter(|variable| !variables.contains(variable))
+                    .cloned()
+                    .collect();
+                variables.extend(added.iter().cloned());
+                return added;
+            }
+            _ => return Vec::new(),
+        };
+    }
+}
+
+/// Remove the columns of the variables added by [`project_bindings`]
+pub fn hide_variables(results: QueryResults, hidden: &[Variable]) -> QueryResults {
+    let QueryResults::Solutions(solutions) = results else {
+        return results;
+    };
+    if hidden.is_empty() {
+        return QueryResults::Solutions(solutions);
+    }
+    let (kept, variables): (Vec<usize>, Vec<Variable>) = solutions
+        .variables()
+        .iter()
+        .enumerate()
+        .filter(|(_, variable)| !hidden.contains(variable))
+        .map(|(index, variable)| (index, variable.clone()))
+        .unzip();
+    let rows = solutions.map(move |solution| {
+        solution.map(|solution| kept.iter().map(|index| solution.values()[*index].clone()).collect())
+    });
+    QueryResults::Solutions(QuerySolutionIter::new(variables.into(), rows))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oxigraph::model::vocab::xsd;
+    use oxigraph::model::Literal;
+    use oxigraph::sparql::QueryOptions;
+    use oxigraph::store::Store;
+
+    fn ns_dict() -> Prefix {
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        ns_dict.add(b"http://www.w3.org/2001/XMLSchema#", b"xsd");
+        ns_dict
+    }
+
+    #[test]
+    fn should_parse_bind_arg() {
+        let binding = parse_bind_arg("?class=ex:Person").unwrap();
+        assert_eq!(binding.variable.as_str(), "class");
+        assert_eq!(binding.value, "ex:Person");
+        assert_eq!(parse_bind_arg("limit=10").unwrap().variable.as_str(), "limit");
+        assert!(parse_bind_arg("?class").is_err());
+    }
+
+    #[test]
+    fn should_bind_terms() {
+        let ns_dict = ns_dict();
+        let person = NamedNode::new("https://example.com/Person").unwrap();
+        assert_eq!(bind_term("ex:Person", &ns_dict), Ok(person.clone().into()));
+        assert_eq!(bind_term("<https://example.com/Person>", &ns_dict), Ok(person.into()));
+        assert_eq!(bind_term("10", &ns_dict), Ok(Literal::new_typed_literal("10", xsd::INTEGER).into()));
+        assert_eq!(
+            bind_term("\"2020\"^^xsd:gYear", &ns_dict),
+            Ok(Literal::new_typed_literal("2020", xsd::G_YEAR).into())
+        );
+        assert_eq!(
+            bind_term("\"chat\"@fr", &ns_dict),
+            Ok(Literal::new_language_tagged_literal("chat", "fr").unwrap().into())
+        );
+        assert!(bind_term("foaf:Person", &ns_dict).is_err());
+        assert!(bind_term("Person", &ns_dict).is_err());
+    }
+
+    #[test]
+    fn should_substitute_without_injection() {
+        let store = Store::new().unwrap();
+        store
+            .update("INSERT DATA { <https://example.com/a> <https://example.com/name> \"a\" }")
+            .unwrap();
+        let query = "SELECT ?s { ?s <https://example.com/name> ?name }";
+        let solutions = |value: &str| {
+            let bindings = vec![(Variable::new("name").unwrap(), bind_term(value, &ns_dict()).unwrap())];
+            let mut parsed = Query::parse(query, None).unwrap();
+            let hidden = project_bindings(&mut parsed, &bindings);
+            let results = store
+                .query_opt_with_substituted_variables(
+                    oxigraph::sparql::Query::from(parsed),
+                    QueryOptions::default(),
+                    bindings,
+                )
+                .unwrap();
+            match hide_variables(results, &hidden) {
+                QueryResults::Solutions(solutions) => {
+                    assert_eq!(solutions.variables(), [Variable::new("s").unwrap()]);
+                    solutions.count()
+                }
+                _ => 0,
+            }
+        };
+        assert_eq!(solutions("\"a\""), 1);
+        // the quotes of the value stay in the literal instead of ending the query
+        assert_eq!(solutions("\"a\\\" } UNION { ?s ?p ?o\""), 0);
+        assert!(uses_variable(query, &Variable::new("name").unwrap()));
+        assert!(!uses_variable(query, &Variable::new("na").unwrap()));
+    }
+}
@@ -0,0 +1,245 @@
+use oxigraph::io::{RdfFormat, RdfParseError};
+use oxigraph::store::{LoaderError, Store};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::format::{format_from_path, open_data_file};
+use crate::load::{
+    data_files, data_parser, load_stdin, too_many_issues, LoadStatus, LoadSummary,
+    MAX_ISSUES_PER_FILE, STDIN_DATA,
+};
+use crate::prefix::{Prefix, PrefixReader};
+use crate::report::LoadIssue;
+use crate::source::base_iri;
+use crate::walk::WalkOptions;
+
+/// Number of quads a loading thread counts before updating the progress line
+const PROGRESS_BATCH: u64 = 10_000;
+
+/// Memory in megabytes shared by the bulk loaders when `--max-memory` is not given
+pub const DEFAULT_MAX_MEMORY: usize = 4096;
+
+/// A bulk loader needs a thread to parse and one to load
+const LOADER_MIN_THREADS: usize = 2;
+
+/// Default number of files loaded at the same time, so the loaders get
+/// [`LOADER_MIN_THREADS`] CPUs each
+pub fn default_jobs(cpus: usize) -> usize {
+    (cpus / LOADER_MIN_THREADS).max(1)
+}
+
+/// Settings of the `--bulk` loading path
+pub struct BulkOptions {
+    pub format: Option<RdfFormat>,
+    /// Number of files loaded at the same time
+    pub jobs: usize,
+    /// CPUs shared by the threads of the bulk loaders
+    pub cpus: usize,
+    /// Memory shared by the bulk loaders, in megabytes
+    pub max_memory: usize,
+    /// Skip the invalid statements instead of failing the file
+    pub lenient: bool,
+    /// Base IRI of the relative IRIs in the data, defaults to the `file://` IRI of each file
+    pub base: Option<String>,
+}
+
+/// Counters shared between the loading threads and the progress line
+struct Progress {
+    triples: AtomicU64,
+    files_done: AtomicUsize,
+    files: usize,
+    done: AtomicBool,
+}
+
+impl Progress {
+    fn print(&self, started: Instant) {
+        let triples = self.triples.load(Ordering::Relaxed);
+        let seconds = started.elapsed().as_secs_f64().max(0.001);
+        eprint!(
+            "\r{}/{} files, {} triples, {:.0} triples/s   ",
+            self.files_done.load(Ordering::Relaxed),
+            self.files,
+            triples,
+            triples as f64 / seconds
+        );
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Stream every data file into the store with the oxigraph bulk loader, several files
+/// in parallel. The bulk loader skips transactions, so a failing file may be left
+/// half loaded. Prefixes are picked up while the files are parsed
+pub fn bulk_load(
+    store: &Store,
+    data: &[String],
+    walk_options: &WalkOptions,
+    options: &BulkOptions,
+    ns_dict: &mut Prefix,
+) -> LoadSummary {
+    let mut queue = VecDeque::new();
+    let mut summary = LoadSummary::default();
+    for data in data {
+        // stdin can only be read once, it is loaded before the files
+        if data == STDIN_DATA {
+            let status = load_stdin(
+                store,
+                std::io::stdin().lock(),
+                options.format,
+                options.base.as_deref(),
+                options.lenient,
+                ns_dict,
+                &mut summary.issues,
+            );
+            summary.count(Some(status));
+            continue;
+        }
+        match data_files(data, walk_options) {
+            Ok((_, paths)) => queue.extend(paths),
+            Err(e) => println!("File does not exist: {}\n with error {}", data, e),
+        }
+    }
+
+    let progress = Progress {
+        triples: AtomicU64::new(0),
+        files_done: AtomicUsize::new(0),
+        files: queue.len(),
+        done: AtomicBool::new(false),
+    };
+    let jobs = options.jobs.clamp(1, queue.len().max(1));
+    let queue = Mutex::new(queue);
+    let found_prefixes = Mutex::new(Vec::new());
+    let issues = Mutex::new(Vec::new());
+    let loaded = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    let started = Instant::now();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            while !progress.done.load(Ordering::Relaxed) {
+                progress.print(started);
+                thread::sleep(Duration::from_millis(500));
+            }
+        });
+
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| loop {
+                    let Some(path) = queue.lock().unwrap().pop_front() else {
+                        return;
+                    };
+                    let mut file_issues = Vec::new();
+                    let (status, prefixes) =
+                        bulk_load_file(store, path, options, jobs, &progress, &mut file_issues);
+                    match status {
+                        Some(LoadStatus::Failed) => failed.fetch_add(1, Ordering::Relaxed),
+                        Some(_) => loaded.fetch_add(1, Ordering::Relaxed),
+                        None => 0,
+                    };
+                    if let Some(prefixes) = prefixes {
+                        found_prefixes.lock().unwrap().push(prefixes);
+                    }
+                    issues.lock().unwrap().append(&mut file_issues);
+                    progress.files_done.fetch_add(1, Ordering::Relaxed);
+                })
+            })
+            .collect();
+        for worker in workers {
+            let _ = worker.join();
+        }
+        progress.done.store(true, Ordering::Relaxed);
+    });
+    progress.print(started);
+    eprintln!();
+
+    for prefixes in found_prefixes.into_inner().unwrap() {
+        ns_dict.extend(&prefixes);
+    }
+    summary.loaded += loaded.into_inner();
+    summary.failed += failed.into_inner();
+    summary.issues.append(&mut issues.into_inner().unwrap());
+    summary
+}
+
+/// Load one file with a bulk loader of its own, returning the prefixes it declares.
+/// The prefixes of a failed file are kept, the statements before the error are loaded
+fn bulk_load_file(
+    store: &Store,
+    path: PathBuf,
+    options: &BulkOptions,
+    jobs: usize,
+    progress: &Progress,
+    issues: &mut Vec<LoadIssue>,
+) -> (Option<LoadStatus>, Option<Prefix>) {
+    let Some(name) = path.file_name().map(|name| name.to_ascii_lowercase()) else {
+        return (None, None);
+    };
+    let file_name = path.to_string_lossy();
+    let Some(rdf_format) = options.format.or_else(|| format_from_path(&path)) else {
+        println!("\nSkipping {:?}: unknown RDF format", name);
+        return (None, None);
+    };
+    let base = options.base.clone().unwrap_or_else(|| base_iri(&path));
+    let parser = match data_parser(rdf_format, &base) {
+        Ok(parser) => parser,
+        Err(e) => {
+            issues.push(LoadIssue::new(&file_name, format!("invalid base IRI: {}", e)));
+            return (Some(LoadStatus::Failed), None);
+        }
+    };
+    let file = match open_data_file(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            issues.push(LoadIssue::new(&file_name, e));
+            return (Some(LoadStatus::Failed), None);
+        }
+    };
+
+    // the CPUs and the memory are split between the files loaded at the same time
+    let loader = store
+        .bulk_loader()
+        .with_num_threads((options.cpus / jobs).max(LOADER_MIN_THREADS))
+        .with_max_memory_size_in_megabytes((options.max_memory / jobs).max(1));
+
+    // quads are counted locally and added to the shared counter in batches
+    let mut counted = 0;
+    let mut skipped = Vec::new();
+    let mut reader = PrefixReader::new(file);
+    let quads = parser
+        .rename_blank_nodes()
+        .for_reader(&mut reader)
+        // in lenient mode the syntax errors are set aside instead of stopping the loader,
+        // the file is given up at too many of them like with the transactional loading
+        .map_while(|quad| match quad {
+            Err(e @ RdfParseError::Syntax(_)) if options.lenient => {
+                skipped.push(LoadIssue::from_parse_error(&file_name, &e));
+                (skipped.len() < MAX_ISSUES_PER_FILE).then_some(None)
+            }
+            quad => Some(Some(quad)),
+        })
+        .flatten()
+        .inspect(|_| {
+            counted += 1;
+            if counted == PROGRESS_BATCH {
+                progress.triples.fetch_add(counted, Ordering::Relaxed);
+                counted = 0;
+            }
+        });
+    let result: Result<(), LoaderError> = loader.load_ok_quads(quads);
+    progress.triples.fetch_add(counted, Ordering::Relaxed);
+    let gave_up = skipped.len() == MAX_ISSUES_PER_FILE;
+    issues.append(&mut skipped);
+    if gave_up {
+        issues.push(too_many_issues(&file_name));
+        return (Some(LoadStatus::Failed), Some(reader.prefixes));
+    }
+    if let Err(e) = result {
+        issues.push(LoadIssue::from_loader_error(&file_name, &e));
+        return (Some(LoadStatus::Failed), Some(reader.prefixes));
+    }
+    (Some(LoadStatus::Loaded), Some(reader.prefixes))
+}
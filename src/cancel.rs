@@ -0,0 +1,222 @@
+use oxigraph::sparql::{EvaluationError, QueryResults, QuerySolutionIter};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running query checks for Ctrl-C and its timeout
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A query is running, Ctrl-C cancels it instead of ending the session
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Ctrl-C was pressed while a query was running
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Why a query did not finish
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stopped {
+    TimedOut,
+    Interrupted,
+    /// The query thread panicked
+    Failed,
+}
+
+/// Let Ctrl-C cancel the running query and return to the prompt. When no query is
+/// running Ctrl-C ends the program like it does without the handler
+pub fn handle_ctrl_c() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if RUNNING.load(Ordering::SeqCst) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            std::process::exit(130);
+        }
+    })
+}
+
+/// Run a query on its own thread and wait for it until the timeout or Ctrl-C.
+/// oxigraph 0.4 can't interrupt the evaluation of a query, so a stopped query is left
+/// to its thread with its cancel flag set: the solutions and triples read through a
+/// [`Cancellable`] iterator and the results written through a [`CancellableWriter`]
+/// fail at the next one, and the thread ends
+pub fn run_cancellable<T: Send + 'static>(
+    timeout: Option<Duration>,
+    work: impl FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+) -> Result<T, Stopped> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = channel();
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let _ = sender.send(work(worker_cancel));
+    });
+
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    RUNNING.store(true, Ordering::SeqCst);
+    let started = Instant::now();
+    let res = loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(value) => break Ok(value),
+            Err(RecvTimeoutError::Disconnected) => break Err(Stopped::Failed),
+            Err(RecvTimeoutError::Timeout) => {
+                if INTERRUPTED.load(Ordering::SeqCst) {
+                    break Err(Stopped::Interrupted);
+                }
+                if timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                    break Err(Stopped::TimedOut);
+                }
+            }
+        }
+    };
+    RUNNING.store(false, Ordering::SeqCst);
+    if res.is_err() {
+        cancel.store(true, Ordering::SeqCst);
+    }
+    res
+}
+
+/// Iterator over the solutions or triples of a query that fails once the query is
+/// cancelled, so the joins, sorts and aggregates behind it are not pulled any further.
+/// A sort or an aggregate still runs to its end once it started
+pub struct Cancellable<I> {
+    inner: I,
+    cancel: Arc<AtomicBool>,
+    stopped: bool,
+}
+
+impl<I> Cancellable<I> {
+    pub fn new(inner: I, cancel: Arc<AtomicBool>) -> Cancellable<I> {
+        Cancellable {
+            inner,
+            cancel,
+            stopped: false,
+        }
+    }
+}
+
+impl<T, I: Iterator<Item = Result<T, EvaluationError>>> Iterator for Cancellable<I> {
+    type Item = Result<T, EvaluationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        if self.cancel.load(Ordering::SeqCst) {
+            // a single error ends the results
+            self.stopped = true;
+            let error = io::Error::other("the query was cancelled");
+            return Some(Err(EvaluationError::ResultsSerialization(error)));
+        }
+        self.inner.next()
+    }
+}
+
+/// Read the solutions of a SELECT query through a [`Cancellable`] iterator, the
+/// other results are returned as they are
+pub fn cancellable_solutions(results: QueryResults, cancel: Arc<AtomicBool>) -> QueryResults {
+    let QueryResults::Solutions(solutions) = results else {
+        return results;
+    };
+    let variables = solutions.variables().into();
+    let rows = solutions.map(|solution| solution.map(|solution| solution.values().to_vec()));
+    QueryResults::Solutions(QuerySolutionIter::new(variables, Cancellable::new(rows, cancel)))
+}
+
+/// Writer that fails once its query is cancelled, to stop writing the results
+pub struct CancellableWriter<W> {
+    inner: W,
+    cancel: Arc<AtomicBool>,
+}
+
+impl<W: Write> CancellableWriter<W> {
+    pub fn new(inner: W, cancel: Arc<AtomicBool>) -> CancellableWriter<W> {
+        CancellableWriter { inner, cancel }
+    }
+
+    fn check(&self) -> io::Result<()> {
+        // not ErrorKind::Interrupted, write_all would retry it forever
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(io::Error::other("the query was cancelled"));
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for CancellableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check()?;
+        self.inner.flush()
+    }
+}
+
+/// Value parser for `--timeout`: a number with a ms, s, m or h unit, seconds without
+pub fn parse_duration_arg(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{value}', expected e.g. 500ms, 30s, 5m or 1h"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.,
+        "" | "s" => number,
+        "m" | "min" => number * 60.,
+        "h" => number * 3600.,
+        unit => return Err(format!("unknown duration unit '{unit}', expected ms, s, m or h")),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_durations() {
+        assert_eq!(parse_duration_arg("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration_arg("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration_arg("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration_arg("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration_arg("2 weeks").is_err());
+        assert!(parse_duration_arg("s").is_err());
+    }
+
+    #[test]
+    fn should_stop_slow_work_at_timeout() {
+        let res = run_cancellable(Some(Duration::from_millis(100)), |cancel| {
+            let mut out = CancellableWriter::new(Vec::new(), cancel);
+            // a query that keeps writing rows until it is cancelled
+            while out.write_all(b"row\n").is_ok() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        assert_eq!(res, Err(Stopped::TimedOut));
+
+        assert_eq!(run_cancellable(Some(Duration::from_secs(10)), |_| 42), Ok(42));
+    }
+
+    #[test]
+    fn should_stop_reading_solutions_once_cancelled() {
+        let store = oxigraph::store::Store::new().unwrap();
+        store
+            .update("INSERT DATA { <https://example.com/a> <https://example.com/p> 1, 2, 3 }")
+            .unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let results = store.query("SELECT ?o { ?s ?p ?o }").unwrap();
+        let QueryResults::Solutions(mut solutions) = cancellable_solutions(results, cancel.clone()) else {
+            panic!("not solutions");
+        };
+        assert!(solutions.next().unwrap().is_ok());
+        cancel.store(true, Ordering::SeqCst);
+        assert!(solutions.next().unwrap().is_err());
+        assert!(solutions.next().is_none());
+    }
+}
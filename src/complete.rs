@@ -0,0 +1,238 @@
+use oxigraph::model::Term;
+use oxigraph::sparql::{Query, QueryResults};
+use oxigraph::store::Store;
+use rustyline::completion::Completer;
+use rustyline::Context;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::prefix::Prefix;
+
+/// Keywords and functions offered by the completion, with the commands of the REPL
+const KEYWORDS: [&str; 78] = [
+    "SELECT", "CONSTRUCT", "DESCRIBE", "ASK", "WHERE", "FROM", "NAMED", "PREFIX", "BASE",
+    "DISTINCT", "REDUCED", "OPTIONAL", "FILTER", "UNION", "MINUS", "GRAPH", "SERVICE", "BIND",
+    "VALUES", "AS", "GROUP", "BY", "ORDER", "HAVING", "LIMIT", "OFFSET", "ASC", "DESC", "COUNT",
+    "SUM", "MIN", "MAX", "AVG", "SAMPLE", "GROUP_CONCAT", "SEPARATOR", "INSERT", "DELETE", "DATA",
+    "WITH", "USING", "LOAD", "CLEAR", "DROP", "CREATE", "ADD", "MOVE", "COPY", "DEFAULT", "ALL",
+    "SILENT", "INTO", "EXISTS", "NOT", "IN", "STR", "LANG", "LANGMATCHES", "DATATYPE", "BOUND",
+    "IRI", "BNODE", "isIRI", "isBLANK", "isLITERAL", "REGEX", "CONTAINS", "STRSTARTS",
+    "STRENDS", "STRLEN", "SUBSTR", "LCASE", "UCASE", "CONCAT", "COALESCE", ".explain",
+    ".profile", ".quit",
+];
+
+/// Most IRIs read from the store for the completion by each of the classes, properties
+/// and subjects queries
+const MAX_IRIS: usize = 10000;
+
+/// Tab completion of the REPL: keywords, prefixes, the local names of the IRIs of the
+/// store after a prefix, and the variables of the query being typed
+pub struct SparqlCompleter {
+    store: Store,
+    /// prefix and namespace of the prefixes of the session
+    prefixes: Vec<(String, String)>,
+    /// IRIs of the store, classes first, then properties. They are read on a thread of
+    /// their own so a large store doesn't hold the prompt, none are offered until then
+    iris: Arc<Mutex<Vec<String>>>,
+    /// Number of reads of the IRIs started, a read doesn't replace the IRIs of a newer one
+    reads: Arc<AtomicUsize>,
+}
+
+impl SparqlCompleter {
+    pub fn new(store: Store) -> SparqlCompleter {
+        let completer = SparqlCompleter {
+            store,
+            prefixes: Vec::new(),
+            iris: Arc::new(Mutex::new(Vec::new())),
+            reads: Arc::new(AtomicUsize::new(0)),
+        };
+        completer.data_changed();
+        completer
+    }
+
+    /// Read the IRIs of the store again, after an update or a reload of the data
+    pub fn data_changed(&self) {
+        let read = self.reads.fetch_add(1, Ordering::SeqCst) + 1;
+        let (store, iris, reads) = (self.store.clone(), self.iris.clone(), self.reads.clone());
+        thread::spawn(move || {
+            let found = store_iris(&store);
+            let mut iris = iris.lock().unwrap();
+            if reads.load(Ordering::SeqCst) == read {
+                *iris = found;
+            }
+        });
+    }
+
+    /// Take the prefixes of the next query
+    pub fn update_prefixes(&mut self, ns_dict: &Prefix) {
+        self.prefixes = ns_dict
+            .fetch_namespace_prefix()
+            .into_iter()
+            .map(|(prefix, namespace)| {
+                (
+                    String::from_utf8_lossy(&prefix).into_owned(),
+                    String::from_utf8_lossy(&namespace).into_owned(),
+                )
+            })
+            .collect();
+    }
+
+    fn candidates(&self, word: &str, input: &str) -> Vec<String> {
+        if word.is_empty() {
+            return Vec::new();
+        }
+        if let Some(name) = word.strip_prefix(['?', '$']) {
+            return variables(input)
+                .into_iter()
+                .filter(|variable| variable.starts_with(name) && *variable != name)
+                .map(|variable| format!("{}{}", &word[..1], variable))
+                .collect();
+        }
+        if let Some((prefix, local)) = word.split_once(':') {
+            return self.local_names(prefix, local);
+        }
+
+        let lowercase = word.chars().all(|c| !c.is_uppercase());
+        let keywords = KEYWORDS
+            .iter()
+            .filter(|keyword| keyword.to_lowercase().starts_with(&word.to_lowercase()))
+            .map(|keyword| if lowercase { keyword.to_lowercase() } else { keyword.to_string() });
+        let prefixes = self
+            .prefixes
+            .iter()
+            .filter(|(prefix, _)| prefix.starts_with(word))
+            .map(|(prefix, _)| format!("{prefix}:"));
+        keywords.chain(prefixes).collect()
+    }
+
+    /// The prefixed names of the IRIs of the store in the namespace of the prefix
+    fn local_names(&self, prefix: &str, local: &str) -> Vec<String> {
+        let Some((_, namespace)) = self.prefixes.iter().find(|(known, _)| known == prefix) else {
+            return Vec::new();
+        };
+        let iris = self.iris.lock().unwrap();
+        iris.iter()
+            .filter_map(|iri| iri.strip_prefix(namespace.as_str()))
+            .filter(|name| name.starts_with(local) && is_local_name(name))
+            .map(|name| format!("{prefix}:{name}"))
+            .collect()
+    }
+}
+
+impl Completer for SparqlCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = word_start(line, pos);
+        Ok((start, self.candidates(&line[start..pos], line)))
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '?' | '$' | '.')
+}
+
+/// Start of the word before the cursor
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name_char(*c))
+        .last()
+        .map_or(pos, |(index, _)| index)
+}
+
+/// The names of the variables of a query, in the order they first appear
+fn variables(input: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    input
+        .match_indices(['?', '$'])
+        .filter_map(|(index, _)| {
+            let rest = &input[index + 1..];
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            Some(&rest[..end]).filter(|name| !name.is_empty())
+        })
+        .filter(|name| seen.insert(*name))
+        .collect()
+}
+
+/// A local name that can be written after the prefix without escapes
+fn is_local_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.ends_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// The IRIs of the store in all the graphs: the classes, the properties, then the
+/// subjects
+fn store_iris(store: &Store) -> Vec<String> {
+    let queries = [
+        format!("SELECT DISTINCT ?iri {{ ?s a ?iri FILTER(isIRI(?iri)) }} LIMIT {MAX_IRIS}"),
+        format!("SELECT DISTINCT ?iri {{ ?s ?iri ?o }} LIMIT {MAX_IRIS}"),
+        format!("SELECT DISTINCT ?iri {{ ?iri ?p ?o FILTER(isIRI(?iri)) }} LIMIT {MAX_IRIS}"),
+    ];
+    let mut seen = HashSet::new();
+    let mut iris = Vec::new();
+    for query in queries {
+        let mut query = Query::parse(&query, None).unwrap();
+        query.dataset_mut().set_default_graph_as_union();
+        let Ok(QueryResults::Solutions(solutions)) = store.query(query) else {
+            continue;
+        };
+        for solution in solutions.filter_map(|solution| solution.ok()) {
+            if let Some(Term::NamedNode(iri)) = solution.get("iri") {
+                if seen.insert(iri.as_str().to_string()) {
+                    iris.push(iri.as_str().to_string());
+                }
+            }
+        }
+    }
+    iris
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn completer() -> SparqlCompleter {
+        let store = Store::new().unwrap();
+        store
+            .update(
+                "PREFIX ex: <https://example.com/>
+                INSERT DATA {
+                    ex:alice a ex:Person ; ex:name \"Alice\" .
+                    <https://example.com/a/b> ex:name \"not a local name\" .
+                }",
+            )
+            .unwrap();
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        let mut completer = SparqlCompleter::new(store);
+        completer.update_prefixes(&ns_dict);
+        // without waiting for the read of the IRIs
+        *completer.iris.lock().unwrap() = store_iris(&completer.store);
+        completer
+    }
+
+    #[test]
+    fn should_complete_prefixed_names() {
+        let completer = completer();
+        // the classes come before the properties and the other IRIs
+        assert_eq!(completer.candidates("ex:", ""), ["ex:Person", "ex:name", "ex:alice"]);
+        assert_eq!(completer.candidates("ex:a", ""), ["ex:alice"]);
+        assert!(completer.candidates("foaf:", "").is_empty());
+    }
+
+    #[test]
+    fn should_complete_keywords_and_variables() {
+        let completer = completer();
+        assert_eq!(completer.candidates("OPT", ""), ["OPTIONAL"]);
+        assert_eq!(completer.candidates("e", ""), ["exists", "ex:"]);
+        let query = "SELECT ?person ?name { ?person ex:name ?n";
+        assert_eq!(completer.candidates("?n", query), ["?name"]);
+        assert_eq!(word_start(query, query.len()), query.len() - 2);
+    }
+}
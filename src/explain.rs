@@ -0,0 +1,107 @@
+use clap::ValueEnum;
+use oxigraph::sparql::QueryExplanation;
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// What is done with a query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Write the results
+    Run,
+    /// Write the query plan instead of the results
+    Explain,
+    /// Write the results, then the query plan with the number of results and the time
+    /// spent in each step
+    Profile,
+}
+
+/// How the query plan is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExplainFormat {
+    /// Indented tree
+    Text,
+    /// The JSON of the oxigraph query explanation
+    Json,
+}
+
+/// Write the plan of a query. The number of results and durations are only known
+/// once the results of a profiled query have been read
+pub fn write_explanation(
+    explanation: &QueryExplanation,
+    format: ExplainFormat,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut json = Vec::new();
+    explanation.write_in_json(&mut json)?;
+    if format == ExplainFormat::Json {
+        out.write_all(&json)?;
+        return writeln!(out);
+    }
+
+    let explanation: Value = serde_json::from_slice(&json)?;
+    if let Some(seconds) = explanation["planning duration in seconds"].as_f64() {
+        writeln!(out, "Planning: {}", format_duration(seconds))?;
+    }
+    write_node(&explanation["plan"], 0, out)
+}
+
+fn write_node(node: &Value, depth: usize, out: &mut dyn Write) -> io::Result<()> {
+    let name = node["name"].as_str().unwrap_or_default();
+    write!(out, "{}{}", "  ".repeat(depth), name)?;
+    if let Some(results) = node["number of results"].as_u64() {
+        write!(out, "  [{} results", results)?;
+        if let Some(seconds) = node["duration in seconds"].as_f64() {
+            write!(out, ", {}", format_duration(seconds))?;
+        }
+        write!(out, "]")?;
+    }
+    writeln!(out)?;
+    for child in node["children"].as_array().into_iter().flatten() {
+        write_node(child, depth + 1, out)?;
+    }
+    Ok(())
+}
+
+fn format_duration(seconds: f64) -> String {
+    format!("{:.3} ms", seconds * 1000.)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oxigraph::sparql::QueryOptions;
+    use oxigraph::store::Store;
+
+    fn explain(with_stats: bool, format: ExplainFormat) -> String {
+        let store = Store::new().unwrap();
+        store
+            .update("INSERT DATA { <https://example.com/a> <https://example.com/p> 1, 2 }")
+            .unwrap();
+        let (results, explanation) = store
+            .explain_query_opt("SELECT ?o { ?s ?p ?o }", QueryOptions::default(), with_stats)
+            .unwrap();
+        if let oxigraph::sparql::QueryResults::Solutions(solutions) = results.unwrap() {
+            assert_eq!(solutions.count(), 2);
+        }
+        let mut out = Vec::new();
+        write_explanation(&explanation, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn should_write_plan_tree() {
+        let plan = explain(false, ExplainFormat::Text);
+        assert!(plan.lines().any(|line| line.starts_with("  ")));
+        assert!(!plan.contains("results"));
+    }
+
+    #[test]
+    fn should_write_profile_with_counts() {
+        let profile = explain(true, ExplainFormat::Text);
+        assert!(profile.contains("[2 results, "));
+
+        let json: Value = serde_json::from_str(&explain(true, ExplainFormat::Json)).unwrap();
+        assert_eq!(json["plan"]["number of results"], 2);
+    }
+}
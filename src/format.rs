@@ -0,0 +1,126 @@
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use oxigraph::io::RdfFormat;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+/// Compression of a data file, recognized from the last extension of the file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+pub fn compression_from_path(path: &Path) -> Compression {
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    match ext.to_ascii_lowercase().as_str() {
+        "gz" | "gzip" => Compression::Gzip,
+        "bz2" | "bzip2" => Compression::Bzip2,
+        "zst" | "zstd" => Compression::Zstd,
+        _ => Compression::None,
+    }
+}
+
+/// Decompress the content of a data file on the fly
+pub fn decompress<'a, R: Read + 'a>(reader: R, compression: Compression) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+    })
+}
+
+/// Open a data file for streaming, decompressing it when it has a compression extension
+pub fn open_data_file(path: &Path) -> io::Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(path)?);
+    decompress(file, compression_from_path(path))
+}
+
+/// Maps a format name or a file extension onto the matching oxigraph parser format.
+/// JSON-LD is not handled here because the oxigraph 0.4 parsers do not support it
+pub fn parse_format(name: &str) -> Option<RdfFormat> {
+    match name.to_ascii_lowercase().as_str() {
+        "ttl" | "turtle" => Some(RdfFormat::Turtle),
+        "trig" => Some(RdfFormat::TriG),
+        "nt" | "ntriples" | "n-triples" => Some(RdfFormat::NTriples),
+        "nq" | "nquads" | "n-quads" => Some(RdfFormat::NQuads),
+        "rdf" | "owl" | "xml" | "rdfxml" | "rdf/xml" => Some(RdfFormat::RdfXml),
+        "n3" => Some(RdfFormat::N3),
+        _ => None,
+    }
+}
+
+/// Guess the format of a data file from its extension, looking past a compression
+/// extension like .gz, .bz2 or .zst
+pub fn format_from_path(path: &Path) -> Option<RdfFormat> {
+    let ext = match compression_from_path(path) {
+        Compression::None => path.extension()?,
+        _ => Path::new(path.file_stem()?).extension()?,
+    };
+    parse_format(ext.to_str()?)
+}
+
+/// Value parser for the `--format` argument
+pub fn parse_format_arg(name: &str) -> Result<RdfFormat, String> {
+    parse_format(name).ok_or(format!(
+        "unknown RDF format '{name}', expected one of ttl, trig, nt, nq, rdf, n3"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_detect_format_from_extension() {
+        assert_eq!(format_from_path(Path::new("data/a.ttl")), Some(RdfFormat::Turtle));
+        assert_eq!(format_from_path(Path::new("data/a.trig")), Some(RdfFormat::TriG));
+        assert_eq!(format_from_path(Path::new("data/a.nq")), Some(RdfFormat::NQuads));
+        assert_eq!(format_from_path(Path::new("data/a.NT")), Some(RdfFormat::NTriples));
+        assert_eq!(format_from_path(Path::new("onto.owl")), Some(RdfFormat::RdfXml));
+        assert_eq!(format_from_path(Path::new("rules.n3")), Some(RdfFormat::N3));
+    }
+
+    #[test]
+    fn should_detect_format_of_compressed_files() {
+        assert_eq!(format_from_path(Path::new("dump/a.ttl.gz")), Some(RdfFormat::Turtle));
+        assert_eq!(format_from_path(Path::new("dump/a.nt.bz2")), Some(RdfFormat::NTriples));
+        assert_eq!(compression_from_path(Path::new("dump/a.nq.zst")), Compression::Zstd);
+        assert_eq!(format_from_path(Path::new("dump/a.gz")), None);
+    }
+
+    #[test]
+    fn should_decompress_gzip() {
+        use flate2::{write::GzEncoder, Compression as Level};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(b"<https://example.com/a> <https://example.com/p> 1 .").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut content = String::new();
+        decompress(compressed.as_slice(), Compression::Gzip)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "<https://example.com/a> <https://example.com/p> 1 .");
+    }
+
+    #[test]
+    fn should_not_detect_unknown_files() {
+        assert_eq!(format_from_path(Path::new("README.md")), None);
+        assert_eq!(format_from_path(Path::new("Makefile")), None);
+        assert_eq!(format_from_path(Path::new("context.jsonld")), None);
+    }
+
+    #[test]
+    fn should_reject_unknown_format_argument() {
+        assert_eq!(parse_format_arg("turtle"), Ok(RdfFormat::Turtle));
+        assert!(parse_format_arg("csv").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{Literal, Subject, Term, Triple};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::prefix::Prefix;
+
+/// Write triples as a JSON-LD document compacted with the known prefixes.
+/// oxigraph 0.4 has no JSON-LD serializer, so the document is built by hand: the
+/// prefixes are the @context and every subject is a node of the @graph, in the order
+/// the subjects are first seen. Quoted triples are written as JSON-LD-star nodes
+pub fn write_jsonld(
+    triples: impl IntoIterator<Item = Triple>,
+    ns_dict: &mut Prefix,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut context = Map::new();
+    for (prefix, namespace) in ns_dict.fetch_namespace_prefix() {
+        context.insert(
+            String::from_utf8_lossy(&prefix).into_owned(),
+            Value::String(String::from_utf8_lossy(&namespace).into_owned()),
+        );
+    }
+
+    let mut subjects: HashMap<Subject, usize> = HashMap::new();
+    let mut nodes: Vec<Map<String, Value>> = Vec::new();
+    for triple in triples {
+        let index = *subjects.entry(triple.subject.clone()).or_insert_with(|| {
+            let mut node = Map::new();
+            node.insert("@id".to_string(), subject_id(&triple.subject, ns_dict));
+            nodes.push(node);
+            nodes.len() - 1
+        });
+        let node = &mut nodes[index];
+        // the classes of a node are its @type
+        let (key, value) = match &triple.object {
+            Term::NamedNode(class) if triple.predicate == rdf::TYPE => {
+                ("@type".to_string(), Value::String(ns_dict.shorten_uri(class.as_str())))
+            }
+            object => (
+                ns_dict.shorten_uri(triple.predicate.as_str()),
+                object_value(object, ns_dict),
+            ),
+        };
+        match node.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+            Value::Array(values) => values.push(value),
+            _ => unreachable!("the values of a property are always an array"),
+        }
+    }
+
+    let document = json!({
+        "@context": context,
+        "@graph": nodes,
+    });
+    serde_json::to_writer_pretty(&mut *out, &document)?;
+    writeln!(out)
+}
+
+fn subject_id(subject: &Subject, ns_dict: &mut Prefix) -> Value {
+    match subject {
+        Subject::NamedNode(node) => Value::String(ns_dict.shorten_uri(node.as_str())),
+        Subject::BlankNode(node) => Value::String(node.to_string()),
+        Subject::Triple(triple) => quoted_triple(triple, ns_dict),
+    }
+}
+
+fn object_value(object: &Term, ns_dict: &mut Prefix) -> Value {
+    match object {
+        Term::NamedNode(node) => json!({ "@id": ns_dict.shorten_uri(node.as_str()) }),
+        Term::BlankNode(node) => json!({ "@id": node.to_string() }),
+        Term::Literal(literal) => literal_value(literal, ns_dict),
+        Term::Triple(triple) => json!({ "@id": quoted_triple(triple, ns_dict) }),
+    }
+}
+
+fn literal_value(literal: &Literal, ns_dict: &mut Prefix) -> Value {
+    if let Some(language) = literal.language() {
+        return json!({ "@value": literal.value(), "@language": language });
+    }
+    if literal.datatype() == xsd::STRING {
+        return Value::String(literal.value().to_string());
+    }
+    json!({
+        "@value": literal.value(),
+        "@type": ns_dict.shorten_uri(literal.datatype().as_str()),
+    })
+}
+
+/// The embedded node of JSON-LD-star, the quoted triple is a node with one property
+fn quoted_triple(triple: &Triple, ns_dict: &mut Prefix) -> Value {
+    let mut node = Map::new();
+    node.insert("@id".to_string(), subject_id(&triple.subject, ns_dict));
+    node.insert(
+        ns_dict.shorten_uri(triple.predicate.as_str()),
+        object_value(&triple.object, ns_dict),
+    );
+    Value::Object(node)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oxigraph::model::NamedNode;
+
+    #[test]
+    fn should_compact_with_prefixes() {
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        let a = NamedNode::new("https://example.com/a").unwrap();
+        let p = NamedNode::new("https://example.com/p").unwrap();
+        let class = NamedNode::new("https://example.com/Thing").unwrap();
+        let triples = vec![
+            Triple::new(a.clone(), rdf::TYPE, class),
+            Triple::new(a.clone(), p.clone(), Literal::new_simple_literal("one")),
+            Triple::new(a, p, Literal::new_language_tagged_literal("un", "fr").unwrap()),
+        ];
+
+        let mut out = Vec::new();
+        write_jsonld(triples, &mut ns_dict, &mut out).unwrap();
+        let document: Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(document["@context"]["ex"], "https://example.com/");
+        let node = &document["@graph"][0];
+        assert_eq!(node["@id"], "ex:a");
+        assert_eq!(node["@type"], json!(["ex:Thing"]));
+        assert_eq!(node["ex:p"], json!(["one", { "@value": "un", "@language": "fr" }]));
+    }
+}
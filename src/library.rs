@@ -0,0 +1,285 @@
+use oxigraph::model::{Term, Variable};
+use oxigraph::sparql::{Query, QueryResults};
+use oxigraph::store::Store;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::bind::parse_bind_arg;
+
+/// Extensions of the query files of a queries directory
+const QUERY_EXTENSIONS: [&str; 3] = ["rq", "ru", "sparql"];
+
+/// A named query of the library
+#[derive(Debug)]
+pub struct LibraryQuery {
+    pub name: String,
+    pub description: Option<String>,
+    pub params: Vec<Param>,
+    pub source: QuerySource,
+}
+
+/// Where the text of a query is
+#[derive(Debug)]
+pub enum QuerySource {
+    /// A file of the queries directory
+    File(PathBuf),
+    /// A query stored in the database, the text of the query
+    Store(String),
+}
+
+/// A parameter declared with # param: ?name description, or ?name=default description
+#[derive(Debug, PartialEq, Eq)]
+pub struct Param {
+    pub variable: Variable,
+    pub default: Option<String>,
+    pub description: Option<String>,
+}
+
+/// What the header comments of a query declare
+#[derive(Debug, Default)]
+struct Header {
+    name: Option<String>,
+    description: Option<String>,
+    params: Vec<Param>,
+}
+
+/// Read the comments at the top of a query:
+///
+/// ```sparql
+/// # name: people-by-class
+/// # description: The people of a class, oldest first
+/// # param: ?class The class of the people
+/// # param: ?min=18 Minimum age
+/// ```
+fn parse_header(text: &str) -> Header {
+    let mut header = Header::default();
+    let comments = text
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix('#'));
+    for comment in comments {
+        let Some((key, value)) = comment.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "name" => header.name = Some(value.to_string()),
+            // a description can go on over several lines
+            "description" => match &mut header.description {
+                Some(description) => {
+                    description.push(' ');
+                    description.push_str(value);
+                }
+                None => header.description = Some(value.to_string()),
+            },
+            "param" => {
+                if let Some(param) = parse_param(value) {
+                    header.params.push(param);
+                }
+            }
+            _ => (),
+        }
+    }
+    header
+}
+
+fn parse_param(value: &str) -> Option<Param> {
+    let (declaration, description) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+    let description = Some(description.trim().to_string()).filter(|d| !d.is_empty());
+    if declaration.contains('=') {
+        let binding = parse_bind_arg(declaration).ok()?;
+        return Some(Param {
+            variable: binding.variable,
+            default: Some(binding.value),
+            description,
+        });
+    }
+    let name = declaration.strip_prefix(['?', '$']).unwrap_or(declaration);
+    Some(Param {
+        variable: Variable::new(name).ok()?,
+        default: None,
+        description,
+    })
+}
+
+/// The queries of a directory, named by their header or by their file name
+pub fn directory_queries(dir: &Path) -> io::Result<Vec<LibraryQuery>> {
+    let mut queries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_query = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| QUERY_EXTENSIONS.contains(&extension));
+        if !is_query || !path.is_file() {
+            continue;
+        }
+        let header = parse_header(&fs::read_to_string(&path)?);
+        let name = header.name.unwrap_or_else(|| {
+            let stem = path.file_stem().unwrap_or_default();
+            stem.to_string_lossy().into_owned()
+        });
+        queries.push(LibraryQuery {
+            name,
+            description: header.description,
+            params: header.params,
+            source: QuerySource::File(path),
+        });
+    }
+    queries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(queries)
+}
+
+/// The queries stored in a database, as SHACL SPARQL executables named with an
+/// rdfs:label, in any graph
+///
+/// ```turtle
+/// ex:people a sh:SPARQLSelectExecutable ;
+///     rdfs:label "people-by-class" ;
+///     rdfs:comment "The people of a class" ;
+///     sh:select "SELECT ?s { ?s a ?class }" .
+/// ```
+pub fn store_queries(store: &Store) -> Vec<LibraryQuery> {
+    let query = "
+PREFIX sh: <http://www.w3.org/ns/shacl#>
+PREFIX rdfs: <http://www.w3.org/2000/01/rdf-schema#>
+
+SELECT ?name ?text ?description
+WHERE {
+    ?query
+        rdfs:label ?name ;
+        sh:select|sh:ask|sh:construct|sh:update ?text ;
+    .
+    OPTIONAL { ?query rdfs:comment ?description }
+}
+ORDER BY ?name
+        ";
+    let mut query = Query::parse(query, None).unwrap();
+    query.dataset_mut().set_default_graph_as_union();
+    let literal = |term: Option<&Term>| match term {
+        Some(Term::Literal(literal)) => Some(literal.value().to_string()),
+        _ => None,
+    };
+
+    let mut queries: Vec<LibraryQuery> = Vec::new();
+    if let Ok(QueryResults::Solutions(solutions)) = store.query(query) {
+        for solution in solutions.filter_map(|solution| solution.ok()) {
+            let (Some(name), Some(text)) = (literal(solution.get("name")), literal(solution.get("text"))) else {
+                continue;
+            };
+            // a query with several comments is only listed once
+            if queries.last().is_some_and(|last| last.name == name) {
+                continue;
+            }
+            let header = parse_header(&text);
+            queries.push(LibraryQuery {
+                name,
+                description: literal(solution.get("description")).or(header.description),
+                params: header.params,
+                source: QuerySource::Store(text),
+            });
+        }
+    }
+    queries
+}
+
+/// The queries of the queries directory and of the database. A query of the directory
+/// hides a query of the database with the same name
+pub fn library(dir: &Path, store: Option<&Store>) -> Vec<LibraryQuery> {
+    let mut queries = match directory_queries(dir) {
+        Ok(queries) => queries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            println!("Error in reading the queries directory {}: {}", dir.display(), e);
+            Vec::new()
+        }
+    };
+    if let Some(store) = store {
+        for query in store_queries(store) {
+            if !queries.iter().any(|known| known.name == query.name) {
+                queries.push(query);
+            }
+        }
+    }
+    queries
+}
+
+/// Print the name, description and parameters of the queries
+pub fn print_library(queries: &[LibraryQuery]) {
+    if queries.is_empty() {
+        println!("No queries found");
+        return;
+    }
+    let width = queries.iter().map(|query| query.name.len()).max().unwrap_or_default();
+    for query in queries {
+        let source = match &query.source {
+            QuerySource::File(path) => path.display().to_string(),
+            QuerySource::Store(_) => "database".to_string(),
+        };
+        match &query.description {
+            Some(description) => println!("{:<width$}  {}  ({})", query.name, description, source),
+            None => println!("{:<width$}  ({})", query.name, source),
+        }
+        for param in &query.params {
+            let default = param.default.as_ref().map(|d| format!(" = {d}")).unwrap_or_default();
+            let description = param.description.as_deref().unwrap_or_default();
+            println!("{:<width$}    ?{}{}  {}", "", param.variable.as_str(), default, description);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_parse_header() {
+        let text = "# name: people\n\
+            # description: The people of a class,\n\
+            # description: oldest first\n\
+            # param: ?class The class of the people\n\
+            # param: ?min=18\n\
+            SELECT ?s { ?s a ?class } # param: ?not_a_param\n";
+        let header = parse_header(text);
+        assert_eq!(header.name.as_deref(), Some("people"));
+        assert_eq!(header.description.as_deref(), Some("The people of a class, oldest first"));
+        assert_eq!(
+            header.params,
+            vec![
+                Param {
+                    variable: Variable::new("class").unwrap(),
+                    default: None,
+                    description: Some("The class of the people".to_string()),
+                },
+                Param {
+                    variable: Variable::new("min").unwrap(),
+                    default: Some("18".to_string()),
+                    description: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_find_stored_queries() {
+        let store = Store::new().unwrap();
+        store
+            .update(
+                "PREFIX sh: <http://www.w3.org/ns/shacl#>
+                PREFIX rdfs: <http://www.w3.org/2000/01/rdf-schema#>
+                INSERT DATA { GRAPH <https://example.com/g> {
+                    <https://example.com/q> rdfs:label \"count\" ;
+                        sh:select \"# param: ?class\\nSELECT (COUNT(*) AS ?n) { ?s a ?class }\" .
+                } }",
+            )
+            .unwrap();
+        let queries = store_queries(&store);
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].name, "count");
+        assert_eq!(queries[0].params[0].variable.as_str(), "class");
+        assert!(matches!(&queries[0].source, QuerySource::Store(text) if text.contains("COUNT")));
+    }
+}
@@ -0,0 +1,661 @@
+use oxigraph::io::{RdfFormat, RdfParseError};
+use oxigraph::model::{GraphName, IriParseError, NamedNode};
+use oxigraph::store::{StorageError, Store};
+use oxrdfio::RdfParser;
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::{fs, path::Path, path::PathBuf};
+
+use crate::format::{format_from_path, open_data_file};
+use crate::prefix::{Prefix, PrefixReader};
+use crate::report::LoadIssue;
+use crate::source::{
+    base_iri, graph_name, graph_writers, remove_source, stored_sources, SourceFile,
+};
+use crate::walk::{collect_files, WalkOptions};
+
+/// Settings shared by every file that is loaded into the store
+pub struct LoadOptions {
+    /// Overrides the format guessed from the file extension
+    pub format: Option<RdfFormat>,
+    /// Load the default graph of each file into a named graph of its own. The graph is
+    /// only reloaded when the file changed. The named graphs of a TriG or N-Quads file
+    /// are recorded with it and cleared when the file changes or is deleted
+    pub graph_per_file: bool,
+    /// IRI template for the per file graphs, see [`graph_name`]
+    pub graph_template: Option<String>,
+    /// Skip the invalid statements of a file instead of failing the whole file
+    pub lenient: bool,
+    /// Base IRI of the relative IRIs in the data, defaults to the `file://` IRI of each file
+    pub base: Option<String>,
+}
+
+/// A file with more invalid statements than this is given up, even in lenient mode
+pub const MAX_ISSUES_PER_FILE: usize = 100;
+
+/// Quads of a file inserted in one transaction in lenient mode, a larger file is not
+/// loaded at once
+const LOAD_BATCH: usize = 100_000;
+
+/// The issue of a file given up after [`MAX_ISSUES_PER_FILE`] invalid statements
+pub fn too_many_issues(file: &str) -> LoadIssue {
+    LoadIssue::new(file, format!("giving up after {} invalid statements", MAX_ISSUES_PER_FILE))
+}
+
+/// What a pass over the data paths changed in the store
+#[derive(Debug, Default)]
+pub struct LoadSummary {
+    pub loaded: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+    /// The files that failed and the statements skipped in lenient mode
+    pub issues: Vec<LoadIssue>,
+}
+
+impl LoadSummary {
+    pub fn count(&mut self, status: Option<LoadStatus>) {
+        match status {
+            Some(LoadStatus::Loaded) => self.loaded += 1,
+            Some(LoadStatus::Unchanged) => self.unchanged += 1,
+            Some(LoadStatus::Failed) => self.failed += 1,
+            None => (),
+        }
+    }
+
+    /// Whether files were loaded or removed, a failed file is reported by its issues
+    pub fn has_changes(&self) -> bool {
+        self.loaded > 0 || self.removed > 0
+    }
+
+    /// Print the problems found while loading, nothing is printed for a clean load
+    pub fn print_report(&self) {
+        if self.issues.is_empty() {
+            return;
+        }
+        println!(
+            "\nLoad report: {} files loaded, {} files failed, {} problems",
+            self.loaded,
+            self.failed,
+            self.issues.len()
+        );
+        for issue in &self.issues {
+            println!("  {}", issue);
+        }
+        println!();
+    }
+}
+
+/// Why a file could not be loaded, the reason itself is in the issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadFailure {
+    /// The data is invalid, the same file fails the same way next time
+    Syntax,
+    /// Reading the file or writing to the store failed, the file may load next time
+    Transient,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadStatus {
+    Loaded,
+    /// The file is already in its graph with the same content
+    Unchanged,
+    /// The file could not be loaded, the reason is in the issues
+    Failed,
+}
+
+/// Parse a file and insert its quads, the named graphs the quads go to are added to
+/// `graphs`. A file is inserted in one transaction, so nothing of a file that fails is
+/// loaded. In lenient mode the invalid statements are added to `issues` and skipped, the
+/// parsers pick up again at the next statement, and the quads are inserted in
+/// transactions of [`LOAD_BATCH`] quads
+pub fn load_reader(
+    store: &Store,
+    parser: RdfParser,
+    reader: impl Read,
+    file: &str,
+    lenient: bool,
+    issues: &mut Vec<LoadIssue>,
+    graphs: &mut HashSet<NamedNode>,
+) -> Result<(), LoadFailure> {
+    let mut quads = Vec::new();
+    let mut skipped = 0;
+    let mut failed = None;
+    for quad in parser.rename_blank_nodes().for_reader(reader) {
+        match quad {
+            Ok(quad) => {
+                if let GraphName::NamedNode(graph) = &quad.graph_name {
+                    if !graphs.contains(graph) {
+                        graphs.insert(graph.clone());
+                    }
+                }
+                quads.push(quad);
+            }
+            Err(e @ RdfParseError::Syntax(_)) if lenient => {
+                issues.push(LoadIssue::from_parse_error(file, &e));
+                skipped += 1;
+                if skipped == MAX_ISSUES_PER_FILE {
+                    issues.push(too_many_issues(file));
+                    failed = Some(LoadFailure::Syntax);
+                    break;
+                }
+                continue;
+            }
+            Err(e) => {
+                issues.push(LoadIssue::from_parse_error(file, &e));
+                failed = Some(match e {
+                    RdfParseError::Syntax(_) => LoadFailure::Syntax,
+                    RdfParseError::Io(_) => LoadFailure::Transient,
+                });
+                break;
+            }
+        }
+        if lenient && quads.len() == LOAD_BATCH {
+            if let Err(e) = store.extend(quads.drain(..)) {
+                issues.push(LoadIssue::new(file, e));
+                return Err(LoadFailure::Transient);
+            }
+        }
+    }
+    if let Some(failure) = failed.filter(|_| !lenient) {
+        return Err(failure);
+    }
+    if let Err(e) = store.extend(quads) {
+        issues.push(LoadIssue::new(file, e));
+        return Err(LoadFailure::Transient);
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+/// Clear the named graphs a file loaded last time. A graph another file also loaded
+/// into is cleared all the same, so this is reported
+fn clear_named_graphs(
+    store: &Store,
+    graph: &NamedNode,
+    stored: &SourceFile,
+    file: &str,
+    issues: &mut Vec<LoadIssue>,
+) -> Result<(), StorageError> {
+    for named_graph in &stored.graphs {
+        for writer in graph_writers(store, named_graph, graph)? {
+            issues.push(LoadIssue::new(
+                file,
+                format!("{} is also loaded from {}, its quads from that file are dropped", named_graph, writer),
+            ));
+        }
+        store.clear_graph(named_graph)?;
+    }
+    Ok(())
+}
+
+/// Load a file into its own graph, skipping it when the store already holds the same
+/// version of the file. A changed file replaces the previous content of the graph.
+/// A file with invalid data is recorded too, so it is only tried again once it changed.
+/// After a read or store error the hash is left out, so the next pass loads it again
+fn update_graph(
+    store: &mut Store,
+    path: &Path,
+    graph: &NamedNode,
+    parser: RdfParser,
+    ns_dict: &mut Prefix,
+    lenient: bool,
+    issues: &mut Vec<LoadIssue>,
+) -> Result<LoadStatus, String> {
+    let stored = SourceFile::from_store(store, graph).map_err(|e| e.to_string())?;
+    if let Some(stored) = &stored {
+        if !stored.is_stale(path) {
+            return Ok(LoadStatus::Unchanged);
+        }
+    }
+
+    let mut source = SourceFile::read(path).map_err(|e| e.to_string())?;
+
+    // only the modification time changed, the metadata is refreshed so the next run
+    // can skip the file on the cheap check again
+    if let Some(stored) = stored.as_ref().filter(|stored| stored.sha256 == source.sha256) {
+        source.graphs = stored.graphs.clone();
+        source.save_to_store(store, graph).map_err(|e| e.to_string())?;
+        return Ok(LoadStatus::Unchanged);
+    }
+
+    let file = open_data_file(path).map_err(|e| e.to_string())?;
+    let mut reader = PrefixReader::new(file);
+    let file = path.to_string_lossy();
+    store.clear_graph(graph).map_err(|e| e.to_string())?;
+    if let Some(stored) = &stored {
+        clear_named_graphs(store, graph, stored, &file, issues).map_err(|e| e.to_string())?;
+    }
+    let parser = parser.with_default_graph(graph.clone());
+    // the named graphs of a lenient load that gave up part way are recorded too, so the
+    // statements loaded before the error are cleared with the next version
+    let mut graphs = HashSet::new();
+    let res = load_reader(store, parser, &mut reader, &file, lenient, issues, &mut graphs);
+    source.graphs = graphs.into_iter().filter(|named_graph| named_graph != graph).collect();
+    if res == Err(LoadFailure::Transient) {
+        source.sha256 = None;
+    }
+    source.save_to_store(store, graph).map_err(|e| e.to_string())?;
+    if res.is_err() {
+        return Ok(LoadStatus::Failed);
+    }
+    ns_dict.extend(&reader.prefixes);
+    Ok(LoadStatus::Loaded)
+}
+
+/// Drop the graphs of the files below `roots` that were not loaded in this run,
+/// because the file was deleted or is no longer selected.
+/// Returns the number of graphs removed
+pub fn remove_deleted_sources(
+    store: &mut Store,
+    roots: &[PathBuf],
+    loaded: &HashSet<NamedNode>,
+) -> Result<usize, StorageError> {
+    let roots: Vec<PathBuf> = roots
+        .iter()
+        .filter_map(|root| std::path::absolute(root).ok())
+        .collect();
+    let mut removed = 0;
+    for (graph, path) in stored_sources(store)? {
+        if loaded.contains(&graph) || !roots.iter().any(|root| path.starts_with(root)) {
+            continue;
+        }
+        if let Some(source) = SourceFile::from_store(store, &graph)? {
+            for named_graph in &source.graphs {
+                store.remove_named_graph(named_graph)?;
+            }
+        }
+        store.remove_named_graph(&graph)?;
+        remove_source(store, graph.as_ref())?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Load a single data file into the store. `root` is the data directory the file was
+/// found in (or the file itself) and is used to derive the per file graph names.
+/// With `graph_per_file` the name of the graph is added to `loaded`.
+/// The reasons a file failed are added to `issues`
+pub fn update_store(
+    store: &mut Store,
+    path: &Path,
+    root: &Path,
+    ns_dict: &mut Prefix,
+    options: &LoadOptions,
+    loaded: &mut HashSet<NamedNode>,
+    issues: &mut Vec<LoadIssue>,
+) -> Option<LoadStatus> {
+    let name = path.file_name()?.to_ascii_lowercase();
+    let file_name = path.to_string_lossy();
+
+    // an explicit --format wins over the extension of the file
+    let rdf_format = match options.format.or_else(|| format_from_path(path)) {
+        Some(rdf_format) => rdf_format,
+        None if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jsonld")) => {
+            println!("Skipping {:?}: JSON-LD is not supported by oxigraph 0.4", name);
+            return None;
+        }
+        None => {
+            println!("Skipping {:?}: unknown RDF format", name);
+            return None;
+        }
+    };
+    let base = options.base.clone().unwrap_or_else(|| base_iri(path));
+    let parser = match data_parser(rdf_format, &base) {
+        Ok(parser) => parser,
+        Err(e) => {
+            issues.push(LoadIssue::new(&file_name, format!("invalid base IRI: {}", e)));
+            return Some(LoadStatus::Failed);
+        }
+    };
+
+    if options.graph_per_file {
+        let graph = match graph_name(path, root, options.graph_template.as_deref()) {
+            Ok(graph) => graph,
+            Err(e) => {
+                issues.push(LoadIssue::new(&file_name, format!("invalid graph name: {}", e)));
+                return Some(LoadStatus::Failed);
+            }
+        };
+        loaded.insert(graph.clone());
+        return match update_graph(store, path, &graph, parser, ns_dict, options.lenient, issues) {
+            Ok(status) => Some(status),
+            Err(e) => {
+                issues.push(LoadIssue::new(&file_name, e));
+                Some(LoadStatus::Failed)
+            }
+        };
+    }
+
+    let file = match open_data_file(path) {
+        Ok(file) => file,
+        Err(e) => {
+            issues.push(LoadIssue::new(&file_name, e));
+            return Some(LoadStatus::Failed);
+        }
+    };
+
+    // the file is streamed to the parser, the prefixes are found on the way
+    let mut reader = PrefixReader::new(file);
+    let res = load_reader(store, parser, &mut reader, &file_name, options.lenient, issues, &mut HashSet::new());
+    ns_dict.extend(&reader.prefixes);
+    if res.is_err() {
+        return Some(LoadStatus::Failed);
+    }
+
+    Some(LoadStatus::Loaded)
+}
+
+/// The `--data` value that reads the data piped on the standard input
+pub const STDIN_DATA: &str = "-";
+
+/// Parser for a data file, relative IRIs in the data are resolved against `base`
+pub fn data_parser(format: RdfFormat, base: &str) -> Result<RdfParser, IriParseError> {
+    RdfParser::from_format(format).with_base_iri(base)
+}
+
+/// Load the data piped on the standard input (`input`) into the default graph.
+/// There is no file extension to guess from, so the format has to be given.
+/// Without a base the relative IRIs are resolved against the working directory
+pub fn load_stdin(
+    store: &Store,
+    input: impl Read,
+    format: Option<RdfFormat>,
+    base: Option<&str>,
+    lenient: bool,
+    ns_dict: &mut Prefix,
+    issues: &mut Vec<LoadIssue>,
+) -> LoadStatus {
+    let Some(rdf_format) = format else {
+        issues.push(LoadIssue::new("<stdin>", "reading data from stdin needs a --format"));
+        return LoadStatus::Failed;
+    };
+    let base = base.map_or_else(|| base_iri(Path::new(".")), str::to_string);
+    let parser = match data_parser(rdf_format, &base) {
+        Ok(parser) => parser,
+        Err(e) => {
+            issues.push(LoadIssue::new("<stdin>", format!("invalid base IRI: {}", e)));
+            return LoadStatus::Failed;
+        }
+    };
+    let mut reader = PrefixReader::new(input);
+    let res = load_reader(store, parser, &mut reader, "<stdin>", lenient, issues, &mut HashSet::new());
+    ns_dict.extend(&reader.prefixes);
+    if res.is_err() {
+        return LoadStatus::Failed;
+    }
+    LoadStatus::Loaded
+}
+
+/// The files to load for a `--data` path, together with the directory the per file
+/// graph names are relative to. A file given directly is relative to its own directory
+pub fn data_files(data: &str, walk_options: &WalkOptions) -> io::Result<(PathBuf, Vec<PathBuf>)> {
+    let path = Path::new(data);
+    if fs::metadata(path)?.is_dir() {
+        Ok((path.to_path_buf(), collect_files(path, walk_options)))
+    } else {
+        let root = path.parent().unwrap_or(path);
+        Ok((root.to_path_buf(), vec![path.to_path_buf()]))
+    }
+}
+
+/// Load every file and directory given with `--data`, then save the prefixes found in
+/// the files to the store. With `graph_per_file` the graphs of the files that are gone
+/// from the data paths are dropped, so running this again keeps the store in sync
+pub fn load_data(
+    store: &mut Store,
+    data: &[String],
+    walk_options: &WalkOptions,
+    load_options: &LoadOptions,
+    ns_dict: &mut Prefix,
+) -> LoadSummary {
+    let mut summary = LoadSummary::default();
+    // the graphs of the files seen in this pass, the graphs of the other files
+    // below the data paths are dropped after loading
+    let mut loaded_graphs = HashSet::new();
+
+    for data in data {
+        if data == STDIN_DATA {
+            let status = load_stdin(
+                store,
+                io::stdin().lock(),
+                load_options.format,
+                load_options.base.as_deref(),
+                load_options.lenient,
+                ns_dict,
+                &mut summary.issues,
+            );
+            summary.count(Some(status));
+            continue;
+        }
+        match data_files(data, walk_options) {
+            Ok((root, paths)) => {
+                for path in paths {
+                    let status = update_store(
+                        store,
+                        &path,
+                        &root,
+                        ns_dict,
+                        load_options,
+                        &mut loaded_graphs,
+                        &mut summary.issues,
+                    );
+                    summary.count(status);
+                }
+            }
+            Err(e) => println!("File does not exist: {}\n with error {}", data, e),
+        }
+    }
+    if let Err(e) = ns_dict.save_to_store(store) {
+        println!("{:?}", e);
+        panic!("Error in Save to Store");
+    };
+
+    if load_options.graph_per_file && !data.is_empty() {
+        let roots: Vec<PathBuf> = data
+            .iter()
+            .filter(|data| *data != STDIN_DATA)
+            .map(PathBuf::from)
+            .collect();
+        match remove_deleted_sources(store, &roots, &loaded_graphs) {
+            Ok(removed) => summary.removed = removed,
+            Err(e) => println!("Error in removing the deleted files: {}", e),
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+
+    use super::*;
+
+    /// An empty directory for the data files of a test, and the options that load each
+    /// file into its own graph
+    pub(crate) fn fixture(name: &str) -> (PathBuf, LoadOptions) {
+        let root = std::env::temp_dir().join(format!("sparqlite-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let options = LoadOptions {
+            format: None,
+            graph_per_file: true,
+            graph_template: None,
+            lenient: false,
+            base: None,
+        };
+        (root, options)
+    }
+
+    #[test]
+    fn should_only_reload_changed_files() {
+        let (root, mut options) = fixture("load");
+        options.graph_template = Some("https://example.com/graph/{path}".to_string());
+        let file = root.join("data.ttl");
+        fs::write(&file, "<https://example.com/a> <https://example.com/p> [] .").unwrap();
+
+        let mut store = Store::new().unwrap();
+        let mut ns_dict = Prefix::new();
+        let mut load = |store: &mut Store| {
+            let mut loaded = HashSet::new();
+            let status = update_store(store, &file, &root, &mut ns_dict, &options, &mut loaded, &mut Vec::new());
+            (status, loaded)
+        };
+
+        assert_eq!(load(&mut store).0, Some(LoadStatus::Loaded));
+        let size = store.len().unwrap();
+        assert_eq!(load(&mut store).0, Some(LoadStatus::Unchanged));
+        assert_eq!(store.len().unwrap(), size);
+
+        // a reload replaces the graph instead of adding a second blank node
+        fs::write(&file, "<https://example.com/a> <https://example.com/p> [] , [] .").unwrap();
+        let (status, loaded) = load(&mut store);
+        assert_eq!(status, Some(LoadStatus::Loaded));
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(store.len().unwrap(), size + 1);
+
+        fs::remove_file(&file).unwrap();
+        let removed = remove_deleted_sources(&mut store, std::slice::from_ref(&root), &HashSet::new()).unwrap();
+        assert_eq!(removed, 1);
+        assert!(store.is_empty().unwrap());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_load_stdin_with_format() {
+        let data = "@prefix ex: <https://example.com/> .\nex:a ex:p <b> .";
+        let store = Store::new().unwrap();
+        let mut ns_dict = Prefix::new();
+
+        let mut issues = Vec::new();
+        let status = load_stdin(&store, data.as_bytes(), None, None, false, &mut ns_dict, &mut issues);
+        assert_eq!(status, LoadStatus::Failed);
+        assert_eq!(issues, [LoadIssue::new("<stdin>", "reading data from stdin needs a --format")]);
+        assert!(store.is_empty().unwrap());
+
+        let base = Some("https://example.com/data/");
+        let status = load_stdin(&store, data.as_bytes(), Some(RdfFormat::Turtle), base, false, &mut ns_dict, &mut issues);
+        assert_eq!(status, LoadStatus::Loaded);
+        assert!(store
+            .contains(&oxigraph::model::Quad::new(
+                NamedNode::new("https://example.com/a").unwrap(),
+                NamedNode::new("https://example.com/p").unwrap(),
+                NamedNode::new("https://example.com/data/b").unwrap(),
+                GraphName::DefaultGraph,
+            ))
+            .unwrap());
+        assert_eq!(ns_dict.expand_curie("ex:a").as_deref(), Some("https://example.com/a"));
+    }
+
+    #[test]
+    fn should_skip_invalid_statements_in_lenient_mode() {
+        let data = "<https://example.com/a> <https://example.com/p> 1 .\n\
+                    <https://example.com/b> <https://example.com/p> <not an iri> .\n\
+                    <https://example.com/c> <https://example.com/p> 3 .\n";
+        let store = Store::new().unwrap();
+        let parser = || RdfParser::from_format(RdfFormat::Turtle);
+
+        let mut issues = Vec::new();
+        let graphs = &mut HashSet::new();
+        let res = load_reader(&store, parser(), data.as_bytes(), "data.ttl", false, &mut issues, graphs);
+        assert_eq!(res, Err(LoadFailure::Syntax));
+        assert_eq!(issues.len(), 1);
+        assert!(store.is_empty().unwrap());
+
+        let mut issues = Vec::new();
+        assert_eq!(load_reader(&store, parser(), data.as_bytes(), "data.ttl", true, &mut issues, graphs), Ok(()));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+        assert_eq!(store.len().unwrap(), 2);
+
+        let data = "<https://example.com/b> <https://example.com/p> <not an iri> .\n".repeat(MAX_ISSUES_PER_FILE + 1);
+        let mut issues = Vec::new();
+        let res = load_reader(&store, parser(), data.as_bytes(), "data.ttl", true, &mut issues, graphs);
+        assert_eq!(res, Err(LoadFailure::Syntax));
+        assert_eq!(issues.len(), MAX_ISSUES_PER_FILE + 1);
+        assert_eq!(issues.last(), Some(&too_many_issues("data.ttl")));
+    }
+
+    #[test]
+    fn should_not_retry_unchanged_broken_files() {
+        let (root, options) = fixture("broken");
+        let file = root.join("broken.ttl");
+        fs::write(&file, "<https://example.com/a> <https://example.com/p> .").unwrap();
+
+        let mut store = Store::new().unwrap();
+        let mut ns_dict = Prefix::new();
+        let mut load = |store: &mut Store| {
+            let mut summary = LoadSummary::default();
+            let status = update_store(store, &file, &root, &mut ns_dict, &options, &mut HashSet::new(), &mut summary.issues);
+            summary.count(status);
+            summary
+        };
+
+        let summary = load(&mut store);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.issues.len(), 1);
+        // the next pass finds the same broken file and leaves it alone
+        let summary = load(&mut store);
+        assert_eq!(summary.unchanged, 1);
+        assert!(summary.issues.is_empty());
+        assert!(!summary.has_changes());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_retry_files_that_could_not_be_read() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let (root, options) = fixture("truncated");
+        let file = root.join("data.ttl.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"<https://example.com/a> <https://example.com/p> 1 .\n").unwrap();
+        let data = encoder.finish().unwrap();
+        // without the gzip trailer the file ends in a read error
+        fs::write(&file, &data[..data.len() - 8]).unwrap();
+
+        let mut store = Store::new().unwrap();
+        let mut ns_dict = Prefix::new();
+        let mut load = |store: &mut Store| {
+            update_store(store, &file, &root, &mut ns_dict, &options, &mut HashSet::new(), &mut Vec::new())
+        };
+
+        assert_eq!(load(&mut store), Some(LoadStatus::Failed));
+        let graph = graph_name(&file, &root, None).unwrap();
+        let source = SourceFile::from_store(&store, &graph).unwrap().unwrap();
+        assert_eq!(source.sha256, None);
+        // the same file is tried again instead of being skipped as unchanged
+        assert_eq!(load(&mut store), Some(LoadStatus::Failed));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_clear_named_graphs_of_reloaded_files() {
+        let (root, options) = fixture("trig");
+        let file = root.join("data.trig");
+        let write = |version: &str| {
+            let data = format!("GRAPH <https://example.com/g> {{ <https://example.com/a> <https://example.com/p> \"{version}\" }}");
+            // the size changes with the content, so the cheap check sees the change
+            fs::write(&file, data).unwrap();
+        };
+        let named_graph = NamedNode::new("https://example.com/g").unwrap();
+        let count = |store: &Store| store.quads_for_pattern(None, None, None, Some(named_graph.as_ref().into())).count();
+
+        let mut store = Store::new().unwrap();
+        let mut ns_dict = Prefix::new();
+        let mut load = |store: &mut Store| {
+            let mut loaded = HashSet::new();
+            update_store(store, &file, &root, &mut ns_dict, &options, &mut loaded, &mut Vec::new())
+        };
+
+        write("v1");
+        assert_eq!(load(&mut store), Some(LoadStatus::Loaded));
+        write("v22");
+        assert_eq!(load(&mut store), Some(LoadStatus::Loaded));
+        assert_eq!(count(&store), 1);
+
+        fs::remove_file(&file).unwrap();
+        let removed = remove_deleted_sources(&mut store, std::slice::from_ref(&root), &HashSet::new()).unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(count(&store), 0);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
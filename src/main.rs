@@ -1,49 +1,276 @@
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, Subcommand};
 use oxigraph::{
-    model::Term,
+    model::{Term, Variable},
     sparql::QueryResults,
     sparql::QuerySolution,
     sparql::QueryOptions,
+    sparql::Query,
+    sparql::Update,
     store::Store,
-    sparql::{ QuerySolutionIter, QueryTripleIter },
-    sparql::results::{QueryResultsFormat, QueryResultsSerializer },
 };
 
 
 
-use oxigraph::io::{RdfFormat, RdfSerializer};
-use oxrdfio::RdfParser;
+use oxigraph::io::RdfFormat;
 
-use comfy_table::{Table, ContentArrangement};
-use serde_derive::Deserialize;
-use serde_json::Map;
-use std::{fs, str, io::Cursor, path::PathBuf};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, io, str};
 
+mod batch;
+use crate::batch::split_statements;
+mod bind;
+use crate::bind::{bind_term, hide_variables, parse_bind_arg, project_bindings, uses_variable, Binding};
+mod bulk;
+mod cancel;
+mod complete;
+use crate::cancel::{
+    cancellable_solutions, handle_ctrl_c, parse_duration_arg, run_cancellable, Cancellable,
+    CancellableWriter, Stopped,
+};
+use crate::bulk::{bulk_load, default_jobs, BulkOptions, DEFAULT_MAX_MEMORY};
+mod explain;
+use crate::explain::{write_explanation, ExplainFormat, QueryMode};
+mod format;
+use crate::format::parse_format_arg;
+mod jsonld;
+mod library;
+use crate::library::{library, print_library, QuerySource};
+mod load;
+use crate::load::{load_data, LoadOptions, LoadSummary, STDIN_DATA};
+mod output;
+use crate::output::{
+    temp_path, write_atomically, write_results, write_triples, GraphFormat, LiteralStyle,
+    OutputFormat, OutputOptions,
+};
 mod prefix;
 use crate::prefix::{find_prefixes, Prefix};
 mod repl;
-use crate::repl::readlinefn;
+use crate::repl::{history_path, split_command, Repl};
+mod report;
+mod source;
+use crate::source::{base_iri, parse_base_arg};
+mod update;
+use crate::update::count_changes;
+mod walk;
+mod watch;
+use crate::watch::watch;
+use crate::walk::{parse_glob_arg, Symlinks, WalkOptions};
+use globset::Glob;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None )]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a query of the library by its name. The parameters declared in its header
+    /// are given with --bind
+    Run {
+        /// Name of the query, see `sparqlite queries list`
+        name: String,
+
+        #[command(flatten)]
+        args: Box<Args>,
+    },
+    /// Look at the queries of the library
+    Queries {
+        #[command(subcommand)]
+        command: QueriesCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueriesCommand {
+    /// List the queries of the library with their description and parameters
+    List {
+        /// Directory of the query files
+        #[arg(long, default_value = "queries")]
+        queries_dir: PathBuf,
+
+        /// Also list the queries stored in this saved database
+        #[arg(long)]
+        db: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
 struct Args {
-    /// Name of the directory or file for trig/nq files, argument can be repeated
+    /// Name of the directory or file for ttl/trig/nt/nq/rdf/n3 files, optionally
+    /// compressed with .gz, .bz2 or .zst, argument can be repeated.
+    /// Use - to read the data from stdin, together with --format and --query
     #[arg(short, long)]
     data: Vec<String>,
 
+    /// Force the RDF format of the data files (ttl, trig, nt, nq, rdf, n3).
+    /// By default the format is guessed from the file extension
+    #[arg(short, long, value_parser = parse_format_arg)]
+    format: Option<RdfFormat>,
+
+    /// Base IRI the relative IRIs in the data and the query are resolved against.
+    /// By default a file is its own base (file:///path/to/file.ttl) and an inline
+    /// query uses the working directory
+    #[arg(long, value_parser = parse_base_arg)]
+    base: Option<String>,
+
+    /// Only load the files of a data directory matching this glob, relative to the
+    /// directory (e.g. "ontologies/**/*.ttl"), argument can be repeated
+    #[arg(long, value_parser = parse_glob_arg)]
+    include: Vec<Glob>,
+
+    /// Skip the files and sub directories of a data directory matching this glob,
+    /// argument can be repeated
+    #[arg(long, value_parser = parse_glob_arg)]
+    exclude: Vec<Glob>,
+
+    /// How symbolic links inside a data directory are handled
+    #[arg(long, value_enum, default_value_t = Symlinks::Follow)]
+    symlinks: Symlinks,
+
+    /// Load each data file into a named graph of its own and record the path, size,
+    /// modification time and hash of the file about that graph. Implies --union-graph.
+    /// With --db only the files changed since the last run are reloaded, and the graphs
+    /// of deleted files are dropped
+    #[arg(long, action=ArgAction::SetTrue)]
+    graph_per_file: bool,
+
+    /// IRI template for the graph of each file, using {path} (relative to the data
+    /// directory), {name} or {stem}. The default is the file:// IRI of the file
+    #[arg(long)]
+    graph_template: Option<String>,
+
+    /// Query the union of all the graphs as the default graph
+    #[arg(long, action=ArgAction::SetTrue)]
+    union_graph: bool,
+
+    /// Stream the data files into the store with the bulk loader, several files at a
+    /// time, for large dumps. Loading is faster but not transactional
+    #[arg(long, action=ArgAction::SetTrue, conflicts_with_all = ["graph_per_file", "watch"])]
+    bulk: bool,
+
+    /// Number of files loaded in parallel by --bulk, defaults to half the number of CPUs.
+    /// The CPUs are shared between the files, each file gets at least two threads
+    #[arg(long, requires = "bulk")]
+    jobs: Option<usize>,
+
+    /// Memory limit in megabytes shared by the bulk loaders of --bulk
+    #[arg(long, requires = "bulk", default_value_t = DEFAULT_MAX_MEMORY)]
+    max_memory: usize,
+
+    /// Keep watching the data paths after loading and update the store when files are
+    /// created, modified or deleted. The data paths are polled every second. An inline
+    /// or file query is run again on every change. Implies --graph-per-file
+    #[arg(long, action=ArgAction::SetTrue)]
+    watch: bool,
+
+    /// Skip invalid statements and keep loading the rest of the file, the skipped
+    /// statements are listed in the load report
+    #[arg(long, action=ArgAction::SetTrue)]
+    lenient: bool,
+
+    /// Exit with a non-zero code when a file fails to load or a statement is skipped,
+    /// for CI
+    #[arg(long, action=ArgAction::SetTrue)]
+    strict: bool,
+
     /// Name of the file or string for loading the query
     #[arg(short, long)]
     query: Option<String>,
 
+    /// Run all the queries and updates of the --query file in order, each with its own
+    /// block of results. They are separated by ; or by lines starting with #---, the
+    /// rest of the line labels the block. Prefixes declared in a query also apply to
+    /// the next ones
+    #[arg(long, action=ArgAction::SetTrue, requires = "query", conflicts_with = "out")]
+    batch: bool,
+
     /// Print the query before executing
     #[arg(long, action=ArgAction::SetTrue)]
     print_query: bool,
 
+    /// Print the query plan instead of running the query. In the REPL a query can be
+    /// explained with .explain <query>
+    #[arg(long, action=ArgAction::SetTrue, conflicts_with = "profile")]
+    explain: bool,
+
+    /// Run the query, then print the query plan with the number of results and the
+    /// time of each step on stderr. In the REPL use .profile <query>
+    #[arg(long, action=ArgAction::SetTrue)]
+    profile: bool,
+
+    /// Bind a variable of the query to a value, e.g. --bind ?class=ex:Person --bind ?min=10.
+    /// The value is an IRI (<...>), a prefixed name or a literal ("text", "text"@en,
+    /// "2020"^^xsd:gYear, 10, true), argument can be repeated
+    #[arg(long, value_parser = parse_bind_arg)]
+    bind: Vec<Binding>,
+
+    /// Stop queries running longer than this, e.g. 500ms, 30s, 5m. In the REPL a
+    /// running query can also be cancelled with Ctrl-C
+    #[arg(long, value_parser = parse_duration_arg)]
+    timeout: Option<Duration>,
+
+    /// Format of the --explain and --profile plans
+    #[arg(long, value_enum, default_value_t = ExplainFormat::Text)]
+    explain_format: ExplainFormat,
+
+    /// Format of the SELECT and ASK results. json, xml, csv and tsv are the standard
+    /// SPARQL result formats and keep the full IRIs. Defaults to the format of the
+    /// --out extension, or table
+    #[arg(long, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Format of the CONSTRUCT and DESCRIBE results (turtle, ntriples, nquads, trig,
+    /// rdfxml, jsonld, n3), written with the known prefixes. Defaults to the format
+    /// of the --out extension, or turtle
+    #[arg(long, value_enum)]
+    graph_format: Option<GraphFormat>,
+
+    /// Write the results to this file instead of stdout. The file is replaced once
+    /// all the results are written, so it is never left half written. The explanation
+    /// of --explain is written to it too, the profile of --profile stays on stderr
+    #[arg(long)]
+    out: Option<PathBuf>,
+
+    /// Show the full IRIs in the table, markdown and html results instead of
+    /// shortening them with the known prefixes
+    #[arg(long, action=ArgAction::SetTrue)]
+    no_shorten: bool,
+
+    /// Print the table results in tables of this many rows, so large results are
+    /// shown while the query runs. 0 prints one table with all the rows
+    #[arg(long, default_value_t = 1000)]
+    page_size: usize,
+
+    /// How the terms are shown in the table, markdown and html results. turtle shows
+    /// the language tags and datatypes ("chat"@fr, "1"^^xsd:int), plain only the values
+    #[arg(long, value_enum, default_value_t = LiteralStyle::Turtle)]
+    literal_style: LiteralStyle,
+
     /// Use or create a saved database. By specifying the database these will be stored
     /// or they will re-use the exiting database
     #[arg(long)]
     db: Option<String>,
 
+    /// Directory of the query files of the library, for the run subcommand. Queries are
+    /// named by a # name: header comment or by their file name. With --db the queries
+    /// stored in the database as SHACL SPARQL executables are part of the library too
+    #[arg(long, default_value = "queries")]
+    queries_dir: PathBuf,
+
+    /// Allow SPARQL updates (INSERT, DELETE, LOAD, CLEAR...) to change a saved
+    /// database. Updates on the in memory store of a session are always allowed
+    #[arg(long, action=ArgAction::SetTrue)]
+    allow_update: bool,
+
     /// Toggle prefix injection. For inline queries the default
     /// is to inject the prefixes into the query, but for file based queries,
     /// the default is to not inject the prefixes
@@ -51,164 +278,251 @@ struct Args {
     toggle_prefix: bool,
 }
 
-fn update_store(store: &mut Store, path: PathBuf, ns_dict: &mut Prefix) -> Option<()> {
-    let ext = path.extension()?;
-    let name = path.file_name()?.to_ascii_lowercase();
+/// How the queries of a session are run
+#[derive(Clone)]
+struct QuerySettings {
+    /// Print the query before executing
+    print: bool,
+    is_prefix_injected: bool,
+    union_graph: bool,
+    /// Base IRI of the relative IRIs in the query
+    base: String,
+    /// SPARQL updates may change the store
+    allow_update: bool,
+    output: OutputOptions,
+    /// File the results or the explanation are written to, stdout when there is none
+    out: Option<PathBuf>,
+    /// Run, explain or profile the queries
+    mode: QueryMode,
+    explain_format: ExplainFormat,
+    /// Queries running longer are stopped
+    timeout: Option<Duration>,
+    /// Values of the --bind variables, substituted in the parsed query
+    bindings: Vec<(Variable, Term)>,
+}
 
-    if ext.is_empty() {
-        return None;
-    }
-    let file = fs::read(path);
+fn print_query(store: &Store, query: &str, ns_dict: &mut Prefix, settings: &QuerySettings) {
+    let prefix_string = ns_dict.format_for_query();
+    let formatted_query = if settings.is_prefix_injected {
+        format!("{prefix_string}\n\n{query}")
+    } else {
+        query.to_string()
+    };
 
-    if file.is_err() {
-        return None;
+    if settings.print {
+        println!("{}\n\n", formatted_query);
     }
 
-    let file_contents = file.unwrap();
-    find_prefixes(&file_contents, ns_dict);
-    let res = store.load_from_reader(RdfParser::from_format(RdfFormat::Turtle), Cursor::new(&file_contents));
-    if res.is_err() {
-        println!("Error: {:?}", res);
-        println!("Error saving {:?} to store", name);
-        return None;
+    let base = Some(settings.base.as_str());
+    // the query is parsed with spargebra to add the bound variables to its projection
+    // oxigraph fails on a substituted variable that is not in the query
+    let bindings: Vec<(Variable, Term)> = settings
+        .bindings
+        .iter()
+        .filter(|(variable, _)| uses_variable(query, variable))
+        .cloned()
+        .collect();
+    let (mut parsed_query, hidden) = match spargebra::Query::parse(&formatted_query, base) {
+        Ok(mut parsed_query) => {
+            let hidden = project_bindings(&mut parsed_query, &bindings);
+            (Query::from(parsed_query), hidden)
+        }
+        // not a query, but it may be an update
+        Err(e) => match spargebra::Update::parse(&formatted_query, base) {
+            Ok(_) if settings.mode != QueryMode::Run => {
+                println!("Only queries can be explained or profiled, the update was not run");
+                return;
+            }
+            Ok(update) => {
+                run_update(store, update, settings);
+                return;
+            }
+            Err(update_error) if is_update(query) => {
+                println!("Error in parsing the update: {}", update_error);
+                return;
+            }
+            Err(_) => {
+                println!("Error in parsing the query: {}", e);
+                return;
+            }
+        },
+    };
+    // FROM and FROM NAMED clauses in the query win over the union of the graphs
+    if settings.union_graph && parsed_query.dataset().is_default_dataset() {
+        parsed_query.dataset_mut().set_default_graph_as_union();
     }
 
-    Some(())
-}
-
-#[derive(Deserialize)]
-struct SparqlJson {
-    head: HeadJson,
-    results: ResultJson,
-}
-
-#[derive(Deserialize)]
-struct HeadJson {
-    vars: Vec<Box<str>>,
-}
-
-#[derive(Deserialize)]
-struct ResultJson {
-    bindings: Vec<Map<String, serde_json::Value>>,
-}
-
-fn print_select(solutions: QuerySolutionIter, ns_dict: &mut Prefix) {
-
-    let mut writer: Vec<_> = Vec::new();
-    //let res = solutions. .write(&mut writer, QueryResultsFormat::Json);
-    let json_serializer = QueryResultsSerializer::from_format(QueryResultsFormat::Json);
-    let mut serializer = json_serializer.serialize_solutions_to_writer(&mut writer, solutions.variables().to_vec()).unwrap();
-
-    for solution in solutions {
-        serializer.serialize(&solution.unwrap()).unwrap();
+    // the query runs on its own thread, so it can be left behind on timeout or Ctrl-C
+    let worker_store = store.clone();
+    let mut worker_ns_dict = ns_dict.clone();
+    let worker_settings = QuerySettings {
+        bindings,
+        ..settings.clone()
+    };
+    let res = run_cancellable(settings.timeout, move |cancel| {
+        run_query(&worker_store, parsed_query, hidden, &mut worker_ns_dict, &worker_settings, cancel)
+    });
+    // the results file of a stopped query is never renamed, its thread may not get to
+    // remove the temporary file before the program ends
+    if let (Err(_), Some(out)) = (&res, &settings.out) {
+        if let Some(temp_path) = temp_path(out) {
+            let _ = fs::remove_file(temp_path);
+        }
     }
-
-    serializer.finish().unwrap();
-
-
-
-
-    let object: SparqlJson = serde_json::from_slice(&writer).expect("Error in Parsing Json");
-    let vars = object.head;
-
-    let mut table = Table::new();
-    table.set_content_arrangement(ContentArrangement::Dynamic);
-    let headings: Vec<String> =  vars.vars
-            .clone()
-            .into_iter()
-            .map(|x| x.to_string())
-           .collect();
-    // the following loop should really be placed in its own function
-    // perhaps a module and re-write the pretty printing of the table
-    table.set_header(headings);
-    for result in object.results.bindings {
-        let mut print_res  = Vec::new();
-        for var in &vars.vars {
-            if let Some(serde_json::Value::Object(var_map)) = &result.get(&var.to_string()).or(None)
-            {
-                let rdf_type = &var_map["type"];
-                let let_return_value = match rdf_type.as_str() {
-                    Some("uri") => {
-                        let res = ns_dict.shorten_uri(&var_map["value"].to_string());
-                        res
-                    }
-                    Some("literal") => var_map["value"].to_string(),
-                    Some("bnode") => var_map["value"].to_string(),
-                    Some("triple") => format!(
-                        "{}\t{}\t{}",
-                        var_map["subject"], var_map["predicate"], var_map["object"]
-                    ),
-                    _ => continue,
-                };
-                print_res.push(let_return_value);
-            } else {
-                // This happens when there is no particular result for the variable, we need to set a place holder
-                // This allows the cell to be empty
-                print_res.push("".to_string())
-            }
+    match res {
+        Ok(()) => (),
+        Err(Stopped::TimedOut) => {
+            println!("\nThe query timed out after {:?}", settings.timeout.unwrap_or_default())
         }
-        table.add_row(print_res);
+        Err(Stopped::Interrupted) => println!("\nThe query was cancelled"),
+        Err(Stopped::Failed) => println!("Error in running the query"),
     }
-    //table.printstd();
-    println!("{table}");
-    let row_numbers = table.row_count();
-    println!("Total: {}", row_numbers);
 }
 
-
-
-fn print_graph(triples: QueryTripleIter, ns_dict: &Prefix)  {
-
-    let mut tserializer = RdfSerializer::from_format(RdfFormat::Turtle); //.for_writer(Vec::new());
-    for (prefix, namespace) in ns_dict.fetch_namespace_prefix() {
-        tserializer = tserializer.with_prefix(std::str::from_utf8(&prefix).unwrap(), std::str::from_utf8(&namespace).unwrap()).unwrap();
-    }
-
-    let mut serializer = tserializer.for_writer(Vec::new());
-
-
-    for triple in triples {
-        serializer.serialize_triple(triple.unwrap().as_ref()).unwrap();
+/// Run the queries and updates of a batch file one after the other, under a label.
+/// A statement with only PREFIX or BASE declarations is not run, its prefixes are
+/// kept for the next statements
+fn run_batch(store: &Store, script: &str, ns_dict: &mut Prefix, settings: &QuerySettings) {
+    let mut declared = Prefix::new();
+    let mut statements = Vec::new();
+    for statement in split_statements(script) {
+        let text = format!("{}\n{}", declared.format_for_query(), statement.text);
+        find_prefixes(statement.text.as_bytes(), &mut declared);
+        let is_prologue = spargebra::Update::parse(&text, Some(&settings.base))
+            .is_ok_and(|update| update.operations.is_empty());
+        if !is_prologue {
+            statements.push((statement.title(), text));
+        }
     }
 
-    //let final_ser = serializer.finish().unwrap();
-    match str::from_utf8(&serializer.finish().unwrap()) {
-        Ok(res) => println!("{}", res),
-        _ => println!("Error in parsing string")
+    let count = statements.len();
+    for (index, (title, text)) in statements.iter().enumerate() {
+        println!("\n--- [{}/{}] {} ---", index + 1, count, title);
+        print_query(store, text, ns_dict, settings);
     }
 }
 
-fn print_query(
+/// Evaluate a query and write its results, its plan or both. Once the query is
+/// cancelled its results stop at the next row and nothing more is printed
+fn run_query(
     store: &Store,
-    query: &str,
+    query: Query,
+    hidden: Vec<Variable>,
     ns_dict: &mut Prefix,
-    print: bool,
-    is_prefix_injected: bool,
+    settings: &QuerySettings,
+    cancel: Arc<AtomicBool>,
 ) {
-    let prefix_string = ns_dict.format_for_query();
-    let formatted_query = if is_prefix_injected {
-        format!("{prefix_string}\n\n{query}")
-    } else {
-        query.to_string()
+    let report = |message: String| {
+        if !cancel.load(Ordering::SeqCst) {
+            println!("{}", message);
+        }
     };
 
-    if print {
-        println!("{}\n\n", formatted_query);
+    // the number of results and durations are only collected for a profile
+    let with_stats = settings.mode == QueryMode::Profile;
+    let (results, explanation) = match store.explain_query_opt_with_substituted_variables(
+        query,
+        QueryOptions::default(),
+        with_stats,
+        settings.bindings.clone(),
+    ) {
+        Ok(evaluation) => evaluation,
+        Err(e) => return report(format!("Error in running the query: {}", e)),
+    };
+    // stdout is not locked for the whole query, the prompt has to print when it is cancelled
+    let mut stdout = CancellableWriter::new(io::stdout(), cancel.clone());
+    if settings.mode == QueryMode::Explain {
+        let write = |out: &mut dyn io::Write| write_explanation(&explanation, settings.explain_format, out);
+        let res = match &settings.out {
+            Some(path) => write_atomically(path, |out| write(&mut CancellableWriter::new(out, cancel.clone()))),
+            None => write(&mut stdout),
+        };
+        if let Err(e) = res {
+            report(format!("Error in writing the explanation: {}", e));
+        }
+        return;
     }
-
-    let (results, _explanation) = store.explain_query_opt(&formatted_query, QueryOptions::default(), true).unwrap();
-    match results.unwrap() {
-        QueryResults::Solutions(solutions) => {
-            print_select(solutions, ns_dict);
-        },
-        QueryResults::Boolean(result) => {
-            println!("{:?}", result);
-        },
+    let results = match results {
+        Ok(results) => hide_variables(results, &hidden),
+        Err(e) => return report(format!("Error in running the query: {}", e)),
+    };
+    let write = |out: &mut dyn io::Write| match results {
         QueryResults::Graph(triples) => {
-            print_graph(triples, ns_dict);
+            let triples = Cancellable::new(triples, cancel.clone());
+            write_triples(triples, settings.output.graph_format, ns_dict, out)
+        }
+        results => {
+            let results = cancellable_solutions(results, cancel.clone());
+            write_results(results, &settings.output, ns_dict, out)
+        }
+    };
+    let res = match &settings.out {
+        Some(path) => write_atomically(path, |out| write(&mut CancellableWriter::new(out, cancel.clone()))),
+        None => write(&mut stdout),
+    };
+    if let Err(e) = res {
+        return report(format!("Error in writing the results: {}", e));
+    }
+
+    // the profile goes to stderr so it doesn't end up in the results piped to other tools
+    if settings.mode == QueryMode::Profile {
+        let mut stderr = CancellableWriter::new(io::stderr(), cancel.clone());
+        if let Err(e) = write_explanation(&explanation, settings.explain_format, &mut stderr) {
+            report(format!("Error in writing the profile: {}", e));
         }
     }
+}
 
+/// Whether the first keyword after the PREFIX and BASE declarations starts an update,
+/// to report the parse error of the update instead of the one of the query
+fn is_update(query: &str) -> bool {
+    const UPDATE_KEYWORDS: [&str; 10] = [
+        "INSERT", "DELETE", "LOAD", "CLEAR", "CREATE", "DROP", "COPY", "MOVE", "ADD", "WITH",
+    ];
+    // the words of the declarations are the keywords, the prefix names and the IRIs
+    let keyword = query
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .find(|word| {
+            !(word.eq_ignore_ascii_case("PREFIX")
+                || word.eq_ignore_ascii_case("BASE")
+                || word.ends_with(':')
+                || word.contains('<'))
+        });
+    keyword.is_some_and(|word| UPDATE_KEYWORDS.contains(&word.to_ascii_uppercase().as_str()))
+}
+
+/// Run a SPARQL update and report the quads it added and removed, see
+/// [`count_changes`] for the updates that are not counted
+fn run_update(store: &Store, update: spargebra::Update, settings: &QuerySettings) {
+    if !settings.allow_update {
+        println!("Updates are not allowed on a saved database without --allow-update");
+        return;
+    }
+    let changes = count_changes(store, &update);
+    let mut update = Update::from(update);
+    // the WHERE patterns match the same data as a query, USING clauses win over the
+    // union of the graphs
+    if settings.union_graph {
+        for dataset in update.using_datasets_mut() {
+            if dataset.is_default_dataset() {
+                dataset.set_default_graph_as_union();
+            }
+        }
+    }
+    if let Err(e) = store.update(update) {
+        println!("Error in running the update: {}", e);
+        return;
+    }
+    match changes {
+        Some(changes) => println!(
+            "Update done: {} quads added, {} quads removed",
+            changes.added(),
+            changes.removed()
+        ),
+        None => println!("Update done"),
+    }
 }
 
 ///
@@ -270,58 +584,155 @@ WHERE {
 }
 
 fn main() {
-    let args = Args::parse();
+    let cli = Cli::parse();
+    match cli.command {
+        None => run_session(cli.args, None),
+        Some(Command::Run { name, args }) => run_session(*args, Some(name)),
+        Some(Command::Queries {
+            command: QueriesCommand::List { queries_dir, db },
+        }) => {
+            let store = match db.map(Store::open).transpose() {
+                Ok(store) => store,
+                Err(e) => {
+                    println!("Error in opening the database: {}", e);
+                    return;
+                }
+            };
+            print_library(&library(&queries_dir, store.as_ref()));
+        }
+    }
+}
+
+/// Make the query of the library named `name` the query of the session, with the
+/// defaults of the parameters that are not bound
+fn use_library_query(args: &mut Args, name: &str, store: &Store) -> Result<(), String> {
+    if args.query.is_some() {
+        return Err("A query of the library can't be run together with --query".to_string());
+    }
+    let queries = library(&args.queries_dir, args.db.as_ref().map(|_| store));
+    let query = queries
+        .into_iter()
+        .find(|query| query.name == name)
+        .ok_or_else(|| format!("There is no query named {name}, see sparqlite queries list"))?;
+    for param in query.params {
+        if args.bind.iter().any(|binding| binding.variable == param.variable) {
+            continue;
+        }
+        let Some(default) = param.default else {
+            let name = param.variable.as_str();
+            let description = param.description.map(|d| format!(" ({d})")).unwrap_or_default();
+            return Err(format!("Missing parameter ?{name}{description}, give it with --bind ?{name}=value"));
+        };
+        args.bind.push(Binding {
+            variable: param.variable,
+            value: default,
+        });
+    }
+    // a query file is run like a --query file, a stored query like an inline query
+    args.query = Some(match query.source {
+        QuerySource::File(path) => path.to_string_lossy().into_owned(),
+        QuerySource::Store(text) => text,
+    });
+    Ok(())
+}
+
+fn run_session(mut args: Args, name: Option<String>) {
+
+    // stdin can't hold both the data and the query typed at the prompt
+    if args.data.iter().any(|data| data == STDIN_DATA) {
+        if args.format.is_none() {
+            println!("Reading data from stdin needs a --format");
+            return;
+        }
+        if args.query.is_none() && name.is_none() {
+            println!("Reading data from stdin needs a --query");
+            return;
+        }
+    }
+
+    // a saved database is only changed by the loaded files unless updates are allowed
+    let allow_update = args.allow_update || args.db.is_none();
 
     // Store::open is used for an on disk database, it will work even if the the
     // store doesn't exist, Oxigraph will create it
-    let mut store = match args.db {
+    let mut store = match &args.db {
         Some(str) => {
-            let path = std::path::Path::new(&str);
+            let path = std::path::Path::new(str);
             Store::open(path).unwrap()
         }
         // Store::new() will create an in memory store that will drop after the script finishes
         _ => Store::new().unwrap(),
     };
 
+    if let Some(name) = &name {
+        if let Err(e) = use_library_query(&mut args, name, &store) {
+            println!("{}", e);
+            return;
+        }
+    }
+
     let mut ns_dict: Prefix = Prefix::new();
 
-    for data in &args.data {
-        let metadata = fs::metadata(data);
-
-        match metadata {
-            Ok(file_type) => {
-                if file_type.is_dir() {
-                    let paths = fs::read_dir(&data).unwrap();
-                    for path in paths {
-                        if path.is_err() {
-                            println!("Path contains error: {:?}", path);
-                            continue;
-                        };
-                        update_store(&mut store, path.unwrap().path(), &mut ns_dict);
-                    }
-                    if let Err(e) = ns_dict.save_to_store(&mut store) {
-                        println!("{:?}", e);
-                        panic!("Error in Save to Store");
-                    };
-                } else {
-                    update_store(&mut store, PathBuf::from(data), &mut ns_dict);
-                    if let Err(e) = ns_dict.save_to_store(&mut store) {
-                        println!("{:?}", e);
-                        panic!("Error in Save to Store");
-                    };
-                }
-            }
-            Err(e) => println!("File does not exist: {}\n with error {}", data, e),
+    let load_options = LoadOptions {
+        format: args.format,
+        graph_per_file: args.graph_per_file || args.watch,
+        graph_template: args.graph_template.clone(),
+        lenient: args.lenient,
+        base: args.base.clone(),
+    };
+
+    let walk_options = match WalkOptions::new(
+        &args.include,
+        &args.exclude,
+        args.symlinks,
+        args.format.is_some(),
+    ) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error in the include/exclude patterns: {}", e);
+            return;
         }
-    }
+    };
 
-    // if there is a directory supplied, the namespaces are supplied in the files
-    // if there is no directory supplied, it needs to be grabbed from the prefixes stored
-    // in the databases
-    if args.data.len() == 0 {
-        //if &args.data == &None {
-        get_namespaces(&mut ns_dict, &store)
+    let summary = if args.bulk {
+        if args.db.is_some() {
+            println!("Warning: --bulk adds the files to the database again on every run");
+        }
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        let bulk_options = BulkOptions {
+            format: args.format,
+            jobs: args.jobs.unwrap_or_else(|| default_jobs(cpus)),
+            cpus,
+            max_memory: args.max_memory,
+            lenient: args.lenient,
+            base: args.base.clone(),
+        };
+        let summary = bulk_load(&store, &args.data, &walk_options, &bulk_options, &mut ns_dict);
+        if let Err(e) = ns_dict.save_to_store(&mut store) {
+            println!("{:?}", e);
+            panic!("Error in Save to Store");
+        };
+        summary
+    } else {
+        if args.db.is_some() && !args.data.is_empty() && !load_options.graph_per_file {
+            println!("Warning: the files are added to the database again on every run, use --graph-per-file to only reload the changed files");
+        }
+        load_data(&mut store, &args.data, &walk_options, &load_options, &mut ns_dict)
     };
+    summary.print_report();
+    if args.strict && (summary.failed > 0 || !summary.issues.is_empty()) {
+        std::process::exit(1);
+    }
+    if summary.unchanged > 0 || summary.removed > 0 {
+        println!(
+            "{} files unchanged, {} deleted files removed",
+            summary.unchanged, summary.removed
+        );
+    }
+
+    // the namespaces are supplied in the files, but files skipped by an incremental
+    // load and a database without data rely on the prefixes stored in the database
+    get_namespaces(&mut ns_dict, &store);
 
     let length = store.len();
     if length.is_err() || length.unwrap() == 0 {
@@ -329,42 +740,170 @@ fn main() {
         return;
     }
 
-    let query = match args.query {
-        Some(str) => str,
-        None => {
-            let q = readlinefn(&ns_dict);
-            match q {
-                Some(str) => str,
-                None => panic!("Error in readline"),
-            }
+    let union_graph = args.union_graph || load_options.graph_per_file;
+
+    let changes = if args.watch {
+        // the data read from stdin is loaded once, there is nothing to watch
+        let paths = args.data.iter().filter(|data| *data != STDIN_DATA).cloned().collect();
+        Some(watch(store.clone(), paths, walk_options, load_options))
+    } else {
+        None
+    };
+
+    let is_interactive = args.query.is_none();
+    if is_interactive {
+        if let Err(e) = handle_ctrl_c() {
+            println!("Ctrl-C can't cancel the queries: {}", e);
         }
+    }
+
+    let mode = if args.explain {
+        QueryMode::Explain
+    } else if args.profile {
+        QueryMode::Profile
+    } else {
+        QueryMode::Run
     };
 
-    if std::path::Path::new(&query).exists() {
-        let read_file = fs::read_to_string(&query);
-        if read_file.is_err() {
-            println!("There is an error in reading the query file");
-            return;
+    // the values of --bind are known once the prefixes of the data are
+    let mut bindings = Vec::new();
+    for binding in &args.bind {
+        match bind_term(&binding.value, &ns_dict) {
+            Ok(term) => bindings.push((binding.variable.clone(), term)),
+            Err(e) => {
+                println!("Error in --bind ?{}: {}", binding.variable.as_str(), e);
+                return;
+            }
         }
-        print_query(
-            &store,
-            &read_file.unwrap(),
-            &mut ns_dict,
-            args.print_query,
-            !args.toggle_prefix,
-        );
+    }
+
+    let settings = QuerySettings {
+        print: args.print_query,
+        // prefixes are injected in inline queries by default
+        is_prefix_injected: args.toggle_prefix,
+        union_graph,
+        base: args.base.clone().unwrap_or_else(|| base_iri(std::path::Path::new("."))),
+        allow_update,
+        output: OutputOptions {
+            output: args.output.unwrap_or_else(|| {
+                args.out.as_deref().and_then(OutputFormat::from_path).unwrap_or(OutputFormat::Table)
+            }),
+            graph_format: args.graph_format.unwrap_or_else(|| {
+                args.out.as_deref().and_then(GraphFormat::from_path).unwrap_or(GraphFormat::Turtle)
+            }),
+            shorten: !args.no_shorten,
+            page_size: args.page_size,
+            literal_style: args.literal_style,
+        },
+        out: args.out.clone(),
+        mode,
+        explain_format: args.explain_format,
+        timeout: args.timeout,
+        bindings,
+    };
 
+    let Some(query) = args.query else {
+        let history = history_path(args.db.as_deref());
+        run_repl(&store, &mut ns_dict, &settings, args.base.as_deref(), history, changes.as_ref());
         return;
+    };
+
+    let query_path = std::path::Path::new(&query);
+    let is_query_file = query_path.exists();
+    let settings = if is_query_file {
+        QuerySettings {
+            // relative IRIs in a query file are resolved against the file
+            base: args.base.clone().unwrap_or_else(|| base_iri(query_path)),
+            // but the prefixes are not injected by default
+            is_prefix_injected: !args.toggle_prefix,
+            ..settings
+        }
+    } else {
+        settings
+    };
+    let query = if is_query_file {
+        match fs::read_to_string(&query) {
+            Ok(query) => query,
+            Err(_) => {
+                println!("There is an error in reading the query file");
+                return;
+            }
+        }
+    } else {
+        query
+    };
+    for (variable, _) in &settings.bindings {
+        if !uses_variable(&query, variable) {
+            println!("Warning: ?{} is not a variable of the query, it is not bound", variable.as_str());
+        }
+    }
+
+    drain_changes(changes.as_ref(), &mut ns_dict, &store);
+    let batch = args.batch;
+    let run = |ns_dict: &mut Prefix| {
+        if batch {
+            run_batch(&store, &query, ns_dict, &settings)
+        } else {
+            print_query(&store, &query, ns_dict, &settings)
+        }
+    };
+    run(&mut ns_dict);
+
+    if let Some(changes) = changes {
+        for summary in changes {
+            println!(
+                "\nData changed: {} files reloaded, {} deleted files removed\n",
+                summary.loaded, summary.removed
+            );
+            summary.print_report();
+            get_namespaces(&mut ns_dict, &store);
+            run(&mut ns_dict);
+        }
     }
-    // println!("query: {query}");
-
-    print_query(
-        &store,
-        &query,
-        &mut ns_dict,
-        args.print_query,
-        args.toggle_prefix,
-    );
 }
 
+/// The interactive session: read and run queries until .quit or Ctrl-D. The store and
+/// the prefixes stay loaded from one query to the next
+fn run_repl(
+    store: &Store,
+    ns_dict: &mut Prefix,
+    settings: &QuerySettings,
+    base: Option<&str>,
+    history: Option<PathBuf>,
+    changes: Option<&Receiver<LoadSummary>>,
+) {
+    let Some(mut repl) = Repl::new(store, base, history) else {
+        return;
+    };
+    while let Some(input) = repl.read(ns_dict) {
+        // the watcher may have loaded new files while the query was typed
+        let reloaded = drain_changes(changes, ns_dict, store);
+        // a query can be explained or profiled on its own
+        let (mode, query) = split_command(&input).unwrap_or((settings.mode, &input));
+        let settings = QuerySettings {
+            mode,
+            ..settings.clone()
+        };
+        print_query(store, query, ns_dict, &settings);
+        if reloaded || is_update(query) {
+            repl.data_changed();
+        }
+    }
+}
 
+/// Report the files the watcher reloaded since the last query, returns whether there
+/// were any
+fn drain_changes(changes: Option<&Receiver<LoadSummary>>, ns_dict: &mut Prefix, store: &Store) -> bool {
+    let Some(changes) = changes else {
+        return false;
+    };
+    let mut changed = false;
+    for summary in changes.try_iter() {
+        summary.print_report();
+        changed = true;
+    }
+    if changed {
+        get_namespaces(ns_dict, store);
+    }
+    changed
+}
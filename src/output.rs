@@ -0,0 +1,565 @@
+use clap::ValueEnum;
+use comfy_table::{ContentArrangement, Table};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+use oxigraph::io::{RdfFormat, RdfSerializer};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{TermRef, Triple};
+use oxigraph::sparql::{EvaluationError, QueryResults, QuerySolutionIter};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::format::parse_format;
+use crate::jsonld::write_jsonld;
+use crate::prefix::Prefix;
+
+/// How the results of SELECT and ASK queries are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// SPARQL 1.1 query results JSON
+    Json,
+    /// SPARQL query results XML
+    Xml,
+    /// SPARQL 1.1 query results CSV
+    Csv,
+    /// SPARQL 1.1 query results TSV
+    Tsv,
+    /// Table drawn in the terminal
+    Table,
+    /// GitHub flavored markdown table
+    Markdown,
+    /// HTML table
+    Html,
+}
+
+impl OutputFormat {
+    /// Format of a results file named by `--out`, from its extension
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" | "srj" => Some(OutputFormat::Json),
+            "xml" | "srx" => Some(OutputFormat::Xml),
+            "csv" => Some(OutputFormat::Csv),
+            "tsv" => Some(OutputFormat::Tsv),
+            "md" | "markdown" => Some(OutputFormat::Markdown),
+            "html" | "htm" => Some(OutputFormat::Html),
+            "txt" => Some(OutputFormat::Table),
+            _ => None,
+        }
+    }
+
+    /// The standard result formats are written by the oxigraph serializers
+    fn results_format(self) -> Option<QueryResultsFormat> {
+        match self {
+            OutputFormat::Json => Some(QueryResultsFormat::Json),
+            OutputFormat::Xml => Some(QueryResultsFormat::Xml),
+            OutputFormat::Csv => Some(QueryResultsFormat::Csv),
+            OutputFormat::Tsv => Some(QueryResultsFormat::Tsv),
+            _ => None,
+        }
+    }
+
+    /// IRIs are shortened with the known prefixes in the formats read by people,
+    /// the standard formats keep the full IRIs so other tools can read them
+    pub fn shortens_iris(self) -> bool {
+        self.results_format().is_none()
+    }
+}
+
+/// How the triples of CONSTRUCT and DESCRIBE queries are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    #[value(alias = "ttl")]
+    Turtle,
+    #[value(alias = "nt")]
+    Ntriples,
+    /// The triples are in the default graph
+    #[value(alias = "nq")]
+    Nquads,
+    Trig,
+    #[value(alias = "rdf", alias = "xml")]
+    Rdfxml,
+    /// Compacted with the known prefixes as @context
+    Jsonld,
+    N3,
+}
+
+impl GraphFormat {
+    /// Format of a results file named by `--out`, from its extension
+    pub fn from_path(path: &Path) -> Option<GraphFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" | "jsonld" => Some(GraphFormat::Jsonld),
+            _ => match parse_format(&ext)? {
+                RdfFormat::NTriples => Some(GraphFormat::Ntriples),
+                RdfFormat::NQuads => Some(GraphFormat::Nquads),
+                RdfFormat::TriG => Some(GraphFormat::Trig),
+                RdfFormat::RdfXml => Some(GraphFormat::Rdfxml),
+                RdfFormat::N3 => Some(GraphFormat::N3),
+                _ => Some(GraphFormat::Turtle),
+            },
+        }
+    }
+
+    fn rdf_format(self) -> Option<RdfFormat> {
+        match self {
+            GraphFormat::Turtle => Some(RdfFormat::Turtle),
+            GraphFormat::Ntriples => Some(RdfFormat::NTriples),
+            GraphFormat::Nquads => Some(RdfFormat::NQuads),
+            GraphFormat::Trig => Some(RdfFormat::TriG),
+            GraphFormat::Rdfxml => Some(RdfFormat::RdfXml),
+            GraphFormat::N3 => Some(RdfFormat::N3),
+            GraphFormat::Jsonld => None,
+        }
+    }
+}
+
+/// How the terms are shown in the cells of the table, markdown and html results
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LiteralStyle {
+    /// Only the values: chat, 1, https://example.com/a
+    Plain,
+    /// Turtle syntax, with the language tags and datatypes: "chat"@fr, "1"^^xsd:int
+    Turtle,
+}
+
+/// How the results of the queries are written
+#[derive(Clone)]
+pub struct OutputOptions {
+    /// Format of the SELECT and ASK results
+    pub output: OutputFormat,
+    /// Format of the CONSTRUCT and DESCRIBE results
+    pub graph_format: GraphFormat,
+    /// Shorten the IRIs with the known prefixes, in the formats that allow it
+    pub shorten: bool,
+    /// Rows of each table printed, 0 prints all the rows in one table
+    pub page_size: usize,
+    /// How the terms are shown in the table, markdown and html results
+    pub literal_style: LiteralStyle,
+}
+
+/// Write the results of a query, SELECT and ASK results in `output` and CONSTRUCT and
+/// DESCRIBE results in `graph_format`
+pub fn write_results(
+    results: QueryResults,
+    options: &OutputOptions,
+    ns_dict: &mut Prefix,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    match results {
+        QueryResults::Solutions(solutions) => write_solutions(solutions, options, ns_dict, out),
+        QueryResults::Boolean(result) => write_boolean(result, options.output, out),
+        QueryResults::Graph(triples) => write_triples(triples, options.graph_format, ns_dict, out),
+    }
+}
+
+/// Write a results file all at once: the results go to a temporary file next to it,
+/// which replaces the file only when everything was written. A reader of the file
+/// never sees half written results, and a failed query leaves the old file in place
+pub fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let temp_path = temp_path(path).ok_or(io::ErrorKind::InvalidInput)?;
+
+    let res = File::create(&temp_path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        let file = out.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()
+    });
+    match res.and_then(|_| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// The temporary file [`write_atomically`] writes `path` to, hidden next to it
+pub fn temp_path(path: &Path) -> Option<PathBuf> {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name()?);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    Some(path.with_file_name(temp_name))
+}
+
+/// Write the solutions of a SELECT query in the given format. The solutions are
+/// written as they come, only the rows of one table page are held in memory
+pub fn write_solutions(
+    solutions: QuerySolutionIter,
+    options: &OutputOptions,
+    ns_dict: &mut Prefix,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let format = options.output;
+    if let Some(results_format) = format.results_format() {
+        let mut serializer = QueryResultsSerializer::from_format(results_format)
+            .serialize_solutions_to_writer(&mut *out, solutions.variables().to_vec())?;
+        for solution in solutions {
+            serializer.serialize(&solution.map_err(io::Error::other)?)?;
+        }
+        serializer.finish()?;
+        return end_line(format, out);
+    }
+
+    let variables = solutions.variables().to_vec();
+    let headings: Vec<String> = variables.iter().map(|v| v.as_str().to_string()).collect();
+    let shorten = options.shorten && format.shortens_iris();
+    let rows = solutions.map(|solution| {
+        let solution = solution.map_err(io::Error::other)?;
+        Ok(variables
+            .iter()
+            .map(|variable| match solution.get(variable) {
+                Some(term) => cell(term.as_ref(), ns_dict, shorten, options.literal_style),
+                // the variable is not bound in this solution, the cell is left empty
+                None => String::new(),
+            })
+            .collect())
+    });
+    match format {
+        OutputFormat::Markdown => write_markdown(&headings, rows, out),
+        OutputFormat::Html => write_html(&headings, rows, out),
+        _ => write_table(&headings, rows, options.page_size, out),
+    }
+}
+
+/// Write the answer of an ASK query in the given format
+pub fn write_boolean(result: bool, format: OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format.results_format() {
+        Some(results_format) => {
+            QueryResultsSerializer::from_format(results_format).serialize_boolean_to_writer(&mut *out, result)?;
+            end_line(format, out)
+        }
+        None => writeln!(out, "{:?}", result),
+    }
+}
+
+/// Write the triples of a CONSTRUCT or DESCRIBE query in the given format, the
+/// serializers are seeded with the known prefixes
+pub fn write_triples(
+    triples: impl Iterator<Item = Result<Triple, EvaluationError>>,
+    format: GraphFormat,
+    ns_dict: &mut Prefix,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(rdf_format) = format.rdf_format() else {
+        let triples = triples.collect::<Result<Vec<_>, _>>().map_err(io::Error::other)?;
+        return write_jsonld(triples, ns_dict, out);
+    };
+
+    let mut tserializer = RdfSerializer::from_format(rdf_format);
+    for (prefix, namespace) in ns_dict.fetch_namespace_prefix() {
+        let prefix = String::from_utf8_lossy(&prefix);
+        let namespace = String::from_utf8_lossy(&namespace);
+        // a prefix the serializer can't write is left out, the IRIs are written in full
+        if let Ok(with_prefix) = tserializer.clone().with_prefix(prefix, namespace) {
+            tserializer = with_prefix;
+        }
+    }
+
+    let mut serializer = tserializer.for_writer(&mut *out);
+    for triple in triples {
+        serializer.serialize_triple(triple.map_err(io::Error::other)?.as_ref())?;
+    }
+    serializer.finish()?;
+    // like the JSON and XML results, RDF/XML doesn't end with a new line
+    if format == GraphFormat::Rdfxml {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// The JSON and XML serializers don't end the document with a new line
+fn end_line(format: OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        OutputFormat::Json | OutputFormat::Xml => writeln!(out),
+        _ => Ok(()),
+    }
+}
+
+/// How a term is shown in a cell of the tables
+/// Quoted triples are shown as `<< s p o >>`, their terms shown like the other cells
+fn cell(term: TermRef<'_>, ns_dict: &mut Prefix, shorten: bool, style: LiteralStyle) -> String {
+    match (term, style) {
+        (TermRef::NamedNode(node), _) => iri(node.as_str(), ns_dict, shorten, style),
+        (TermRef::BlankNode(node), LiteralStyle::Turtle) => node.to_string(),
+        (TermRef::BlankNode(node), LiteralStyle::Plain) => node.as_str().to_string(),
+        (TermRef::Literal(literal), LiteralStyle::Plain) => literal.value().to_string(),
+        (TermRef::Literal(literal), LiteralStyle::Turtle) => {
+            let value = turtle_string(literal.value());
+            if let Some(language) = literal.language() {
+                format!("{value}@{language}")
+            } else if literal.datatype() == xsd::STRING {
+                value
+            } else {
+                let datatype = iri(literal.datatype().as_str(), ns_dict, shorten, style);
+                format!("{value}^^{datatype}")
+            }
+        }
+        (TermRef::Triple(triple), _) => format!(
+            "<< {} {} {} >>",
+            cell(triple.subject.as_ref().into(), ns_dict, shorten, style),
+            cell(triple.predicate.as_ref().into(), ns_dict, shorten, style),
+            cell(triple.object.as_ref(), ns_dict, shorten, style)
+        ),
+    }
+}
+
+/// An IRI as a prefixed name when it can be shortened, between angle brackets in
+/// Turtle otherwise
+fn iri(iri: &str, ns_dict: &mut Prefix, shorten: bool, style: LiteralStyle) -> String {
+    if shorten {
+        let short = ns_dict.shorten_uri(iri);
+        if short != iri {
+            return short;
+        }
+    }
+    match style {
+        LiteralStyle::Turtle => format!("<{iri}>"),
+        LiteralStyle::Plain => iri.to_string(),
+    }
+}
+
+/// A string between double quotes, escaped like a Turtle string
+fn turtle_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Print the rows in tables of `page_size` rows, so a large result is printed while
+/// it is evaluated instead of being held in memory
+fn write_table(
+    headings: &[String],
+    rows: impl IntoIterator<Item = io::Result<Vec<String>>>,
+    page_size: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let new_table = || {
+        let mut table = Table::new();
+        table.set_content_arrangement(ContentArrangement::Dynamic);
+        table.set_header(headings);
+        table
+    };
+    let mut table = new_table();
+    let mut total = 0;
+    for row in rows {
+        table.add_row(row?);
+        total += 1;
+        if table.row_count() == page_size {
+            writeln!(out, "{table}")?;
+            out.flush()?;
+            table = new_table();
+        }
+    }
+    if table.row_count() > 0 || total == 0 {
+        writeln!(out, "{table}")?;
+    }
+    writeln!(out, "Total: {}", total)
+}
+
+fn write_markdown(
+    headings: &[String],
+    rows: impl IntoIterator<Item = io::Result<Vec<String>>>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let escape = |cell: &String| cell.replace('|', "\\|").replace('\n', "<br>");
+    writeln!(out, "| {} |", headings.iter().map(escape).collect::<Vec<_>>().join(" | "))?;
+    writeln!(out, "|{}", " --- |".repeat(headings.len()))?;
+    for row in rows {
+        writeln!(out, "| {} |", row?.iter().map(escape).collect::<Vec<_>>().join(" | "))?;
+    }
+    Ok(())
+}
+
+fn write_html(
+    headings: &[String],
+    rows: impl IntoIterator<Item = io::Result<Vec<String>>>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "<table>")?;
+    writeln!(out, "  <thead>")?;
+    writeln!(out, "    <tr>")?;
+    for heading in headings {
+        writeln!(out, "      <th>{}</th>", escape_html(heading))?;
+    }
+    writeln!(out, "    </tr>")?;
+    writeln!(out, "  </thead>")?;
+    writeln!(out, "  <tbody>")?;
+    for row in rows {
+        writeln!(out, "    <tr>")?;
+        for cell in row? {
+            writeln!(out, "      <td>{}</td>", escape_html(&cell))?;
+        }
+        writeln!(out, "    </tr>")?;
+    }
+    writeln!(out, "  </tbody>")?;
+    writeln!(out, "</table>")
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oxigraph::model::Term;
+
+    fn render(headings: &[&str], rows: &[&[&str]], format: OutputFormat) -> String {
+        let headings: Vec<String> = headings.iter().map(|h| h.to_string()).collect();
+        let rows = rows
+            .iter()
+            .map(|row| Ok(row.iter().map(|cell| cell.to_string()).collect()));
+        let mut out = Vec::new();
+        match format {
+            OutputFormat::Markdown => write_markdown(&headings, rows, &mut out).unwrap(),
+            OutputFormat::Html => write_html(&headings, rows, &mut out).unwrap(),
+            _ => write_table(&headings, rows, 2, &mut out).unwrap(),
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn should_write_markdown_table() {
+        let res = render(&["s", "label"], &[&["ex:a", "\"a|b\""]], OutputFormat::Markdown);
+        assert_eq!(res, "| s | label |\n| --- | --- |\n| ex:a | \"a\\|b\" |\n");
+    }
+
+    #[test]
+    fn should_print_table_in_pages() {
+        let res = render(&["n"], &[&["1"], &["2"], &["3"]], OutputFormat::Table);
+        assert_eq!(res.matches("| n ").count(), 2);
+        assert!(res.ends_with("Total: 3\n"));
+    }
+
+    #[test]
+    fn should_show_language_tags_and_datatypes() {
+        use oxigraph::model::{Literal, NamedNode};
+
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"http://www.w3.org/2001/XMLSchema#", b"xsd");
+        let mut turtle = |term: Term| cell(term.as_ref(), &mut ns_dict, true, LiteralStyle::Turtle);
+
+        let fr = Literal::new_language_tagged_literal("chat", "fr").unwrap();
+        assert_eq!(turtle(fr.clone().into()), "\"chat\"@fr");
+        let int = Literal::new_typed_literal("1", xsd::INT);
+        assert_eq!(turtle(int.into()), "\"1\"^^xsd:int");
+        assert_eq!(turtle(Literal::new_simple_literal("say \"hi\"\n").into()), r#""say \"hi\"\n""#);
+        assert_eq!(
+            turtle(NamedNode::new("https://example.com/a").unwrap().into()),
+            "<https://example.com/a>"
+        );
+
+        assert_eq!(cell(fr.as_ref().into(), &mut ns_dict, true, LiteralStyle::Plain), "chat");
+    }
+
+    #[test]
+    fn should_show_nested_quoted_triples() {
+        use oxigraph::model::{Literal, NamedNode, Triple};
+
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        let ex = |name: &str| NamedNode::new(format!("https://example.com/{name}")).unwrap();
+        let quoted = Triple::new(ex("s"), ex("p"), ex("o"));
+        let nested = Triple::new(quoted, ex("source"), Literal::new_simple_literal("wiki"));
+        let term = Term::Triple(Box::new(nested));
+
+        assert_eq!(
+            cell(term.as_ref(), &mut ns_dict, true, LiteralStyle::Turtle),
+            "<< << ex:s ex:p ex:o >> ex:source \"wiki\" >>"
+        );
+    }
+
+    #[test]
+    fn should_escape_html_cells() {
+        let res = render(&["s"], &[&["<https://example.com/a?b&c>"]], OutputFormat::Html);
+        assert!(res.contains("<td>&lt;https://example.com/a?b&amp;c&gt;</td>"));
+    }
+
+    #[test]
+    fn should_write_construct_results() {
+        use oxigraph::store::Store;
+
+        let store = Store::new().unwrap();
+        store
+            .update("INSERT DATA { <https://example.com/a> <https://example.com/p> \"one\" }")
+            .unwrap();
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+
+        let mut construct = |format| {
+            let Ok(oxigraph::sparql::QueryResults::Graph(triples)) =
+                store.query("CONSTRUCT WHERE { ?s ?p ?o }")
+            else {
+                panic!("not a graph");
+            };
+            let mut out = Vec::new();
+            write_triples(triples, format, &mut ns_dict, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            construct(GraphFormat::Ntriples),
+            "<https://example.com/a> <https://example.com/p> \"one\" .\n"
+        );
+        assert!(construct(GraphFormat::Turtle).contains("ex:a ex:p \"one\""));
+        assert!(construct(GraphFormat::Jsonld).contains("\"ex:p\": [\n"));
+    }
+
+    #[test]
+    fn should_infer_format_from_out_path() {
+        assert_eq!(OutputFormat::from_path(Path::new("out/results.csv")), Some(OutputFormat::Csv));
+        assert_eq!(OutputFormat::from_path(Path::new("report.MD")), Some(OutputFormat::Markdown));
+        assert_eq!(OutputFormat::from_path(Path::new("graph.ttl")), None);
+        assert_eq!(GraphFormat::from_path(Path::new("graph.ttl")), Some(GraphFormat::Turtle));
+        assert_eq!(GraphFormat::from_path(Path::new("graph.owl")), Some(GraphFormat::Rdfxml));
+        assert_eq!(GraphFormat::from_path(Path::new("graph.jsonld")), Some(GraphFormat::Jsonld));
+        assert_eq!(GraphFormat::from_path(Path::new("results.csv")), None);
+    }
+
+    #[test]
+    fn should_keep_old_file_when_write_fails() {
+        let path = std::env::temp_dir().join(format!("sparqlite-out-{}.csv", std::process::id()));
+        fs::write(&path, "old").unwrap();
+
+        let res = write_atomically(&path, |out| {
+            out.write_all(b"half")?;
+            Err(io::Error::other("query failed"))
+        });
+        assert!(res.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        write_atomically(&path, |out| out.write_all(b"new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let temp_name = format!(".sparqlite-out-{0}.csv.{0}.tmp", std::process::id());
+        assert!(!path.with_file_name(temp_name).exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn should_write_standard_boolean() {
+        let mut out = Vec::new();
+        write_boolean(true, OutputFormat::Json, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"head\":{},\"boolean\":true}\n");
+    }
+}
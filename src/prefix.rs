@@ -5,15 +5,22 @@ use oxigraph::model::vocab::{rdf, xsd};
 use oxigraph::model::{GraphName, Literal, NamedNode, Quad};
 use oxigraph::store::{StorageError, Store};
 use regex::bytes::Regex;
+use std::io::{self, Read};
+use std::sync::OnceLock;
 
 /// Function for Finding Prefixes with Regex
-pub fn find_prefixes(file_contents: &Vec<u8>, ns_dict: &mut Prefix) {
-    let re: Regex = Regex::new(r"(?i)prefix\s+([\w\d\-_]+):\s+<([a-zA-Z0-9\/:\-.#_]+)>").unwrap();
+/// The regex is compiled once, streamed files are scanned chunk by chunk
+pub fn find_prefixes(file_contents: &[u8], ns_dict: &mut Prefix) {
+    static PREFIX_RE: OnceLock<Regex> = OnceLock::new();
+    let re = PREFIX_RE.get_or_init(|| {
+        Regex::new(r"(?i)prefix\s+([\w\d\-_]+):\s+<([a-zA-Z0-9\/:\-.#_]+)>").unwrap()
+    });
     for (_, [prefix, namespace]) in re.captures_iter(file_contents).map(|c| c.extract()) {
         ns_dict.add(namespace, prefix);
     }
 }
 
+#[derive(Clone)]
 pub struct Prefix {
     map: HashMap<Box<[u8]>, Box<[u8]>>,
     pub list: Vec<Box<Vec<u8>>>,
@@ -37,6 +44,15 @@ impl Prefix {
         self.list.push(Box::new(namespace.to_vec()));
     }
 
+    /// Add the prefixes of another dictionary, the namespaces already known keep their prefix
+    pub fn extend(&mut self, other: &Prefix) {
+        for namespace in other.list.iter() {
+            if let Some(prefix) = other.get(namespace) {
+                self.add(namespace, &prefix);
+            }
+        }
+    }
+
     // commenting out this method as we don't need an iterator... yet
     // pub fn iter(&self) -> Iter<Box<[u8]>, Box<[u8]>> {
     //   return self.map.iter();
@@ -119,6 +135,13 @@ impl Prefix {
         return uri.to_string();
     }
 
+    /// The IRI of a prefixed name like ex:Person, None when the prefix is unknown
+    pub fn expand_curie(&self, curie: &str) -> Option<String> {
+        let (prefix, local) = curie.split_once(':')?;
+        let (namespace, _) = self.map.iter().find(|(_, p)| p.as_ref() == prefix.as_bytes())?;
+        Some(format!("{}{}", std::str::from_utf8(namespace).ok()?, local))
+    }
+
     pub fn fetch_namespace_prefix(&self) -> Vec<(Box<[u8]>, Box<Vec<u8>>)> {
         let mut prefixes = Vec::new();
         for namespace in self.list.iter() {
@@ -144,6 +167,54 @@ impl Prefix {
     }
 }
 
+/// A prefix declaration never spans more than this, longer unfinished lines are dropped
+const MAX_PENDING_LINE: usize = 64 * 1024;
+
+/// Reader adapter that finds the prefix declarations in the data streamed through it,
+/// so a file can be scanned for prefixes while it is parsed instead of being read
+/// into memory first. Each chunk is scanned up to its last complete line, the rest is
+/// kept for the next chunk so a declaration cut in two is still found
+pub struct PrefixReader<R> {
+    inner: R,
+    pending: Vec<u8>,
+    pub prefixes: Prefix,
+}
+
+impl<R: Read> PrefixReader<R> {
+    pub fn new(inner: R) -> PrefixReader<R> {
+        PrefixReader {
+            inner,
+            pending: Vec::new(),
+            prefixes: Prefix::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for PrefixReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read == 0 {
+            find_prefixes(&self.pending, &mut self.prefixes);
+            self.pending.clear();
+            return Ok(0);
+        }
+        let chunk = &buf[..read];
+        match chunk.iter().rposition(|byte| *byte == b'\n') {
+            Some(end) => {
+                self.pending.extend_from_slice(&chunk[..=end]);
+                find_prefixes(&self.pending, &mut self.prefixes);
+                self.pending.clear();
+                self.pending.extend_from_slice(&chunk[end + 1..]);
+            }
+            None => self.pending.extend_from_slice(chunk),
+        }
+        if self.pending.len() > MAX_PENDING_LINE {
+            self.pending.clear();
+        }
+        Ok(read)
+    }
+}
+
 fn transform_to_bytes<'a>(uri: &'a str) -> &'a [u8] {
     // let mut uri_bytes = Box::<Vec<u8>>::new(uri.as_bytes().to_owned());
     let mut uri_bytes = uri.as_bytes();
@@ -159,7 +230,8 @@ fn transform_to_bytes<'a>(uri: &'a str) -> &'a [u8] {
 fn match_namespace<'b>(uri: &[u8], namespace: &'b Vec<u8>) -> Option<(&'b Vec<u8>, usize)> {
     let mut i = 0;
     for char in namespace.into_iter() {
-        if &uri[i] != char {
+        // a uri shorter than the namespace can't be in it
+        if uri.get(i) != Some(char) {
             return None;
         }
         i += 1;
@@ -185,6 +257,13 @@ mod tests {
         assert!(res == "ex:_test_example");
     }
     #[test]
+    fn should_expand_curie() {
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        assert_eq!(ns_dict.expand_curie("ex:Person").as_deref(), Some("https://example.com/Person"));
+        assert_eq!(ns_dict.expand_curie("foaf:Person"), None);
+    }
+    #[test]
     fn should_shorten_escaped_quotes() {
         let mut ns_dict = Prefix::new();
         let namespace = "http://www.w3.org/2000/01/rdf-schema#".as_bytes();
@@ -207,6 +286,15 @@ mod tests {
         assert_eq!(res, "premis:fixity")
     }
 
+    #[test]
+    fn should_not_shorten_uri_shorter_than_namespace() {
+        let mut ns_dict = Prefix::new();
+        ns_dict.add("https://example.com/gz/".as_bytes(), "gz".as_bytes());
+
+        let res = ns_dict.shorten_uri("https://example.com/g");
+        assert_eq!(res, "https://example.com/g");
+    }
+
     #[test]
     fn should_return_formatted_prefixes() {
         let mut ns_dict = Prefix::new();
@@ -223,4 +311,19 @@ mod tests {
         let expected_result = "\nPREFIX rdf: <http://www.w3.org/2000/01/rdf-schema#>\nPREFIX ex: <https://example.com/>\n";
         assert_eq!(expected_result, ns_dict.format_for_query());
     }
+
+    #[test]
+    fn should_find_prefixes_split_across_reads() {
+        let data = "@prefix ex: <https://example.com/> .\n@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .\nex:a rdfs:label \"a\" .\n";
+        // a reader handing out 7 bytes at a time cuts the declarations in pieces
+        let mut reader = PrefixReader::new(io::Cursor::new(data));
+        let mut buf = [0; 7];
+        while reader.read(&mut buf).unwrap() > 0 {}
+
+        assert_eq!(reader.prefixes.get(b"https://example.com/").as_deref(), Some(&b"ex"[..]));
+        assert_eq!(
+            reader.prefixes.get(b"http://www.w3.org/2000/01/rdf-schema#").as_deref(),
+            Some(&b"rdfs"[..])
+        );
+    }
 }
@@ -1,15 +1,31 @@
 // use rustyline::error::ReadlineError;
+use crate::complete::SparqlCompleter;
+use crate::explain::QueryMode;
 use crate::prefix::Prefix;
+use crate::source::hex_digest;
 
-use spargebra::Query;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use oxigraph::store::Store;
+use spargebra::{Query, Update};
 
 use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
 use rustyline::validate;
 use rustyline::validate::{ValidationContext, ValidationResult};
-use rustyline::{Completer, Editor, Helper, Highlighter, Hinter, Validator};
+use rustyline::{Completer, Config, Editor, Helper, Highlighter, Hinter, Validator};
+
+/// Stand in base of the validation when no --base is given, the query is run with the
+/// working directory as its base
+const RELATIVE_BASE: &str = "file:///";
 
 #[derive(Completer, Helper, Highlighter, Hinter, Validator)]
 struct InputValidator {
+    #[rustyline(Completer)]
+    completer: SparqlCompleter,
     #[rustyline(Validator)]
     validator: SparqlValidator, //validator: MatchingBracketValidator, //#[rustyline(Highlighter)
                                 //highlighter: MatchingBracketHighlighter,
@@ -17,14 +33,20 @@ struct InputValidator {
 
 #[derive(Default)]
 struct SparqlValidator {
-    _priv: (),
+    /// base IRI so a query with relative IRIs is not taken for an incomplete one
+    base: Option<String>,
+    /// PREFIX declarations shown in the prompt, the typed query uses them
+    prefixes: String,
 }
 
 impl SparqlValidator {
     /// constructor
     #[must_use]
-    pub fn new() -> Self {
-        Self { _priv: () }
+    pub fn new(base: Option<String>) -> Self {
+        Self {
+            base,
+            prefixes: String::new(),
+        }
     }
 }
 
@@ -33,12 +55,42 @@ impl validate::Validator for SparqlValidator {
         &self,
         ctx: &mut ValidationContext,
     ) -> Result<rustyline::validate::ValidationResult, ReadlineError> {
-        validate_sparql_string(ctx.input())
+        validate_sparql_string(ctx.input(), self.base.as_deref(), &self.prefixes)
     }
 }
 
-fn validate_sparql_string(input: &str) -> Result<ValidationResult, ReadlineError> {
-    let query = Query::parse(input, None);
+/// Typed at the prompt to end the session, like Ctrl-D
+const QUIT: &str = ".quit";
+
+/// Number of queries kept in a history file
+const HISTORY_SIZE: usize = 1000;
+
+/// Split the `.explain` and `.profile` commands from the query typed after them
+pub fn split_command(input: &str) -> Option<(QueryMode, &str)> {
+    let input = input.trim_start();
+    let (command, query) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    match command {
+        ".explain" => Some((QueryMode::Explain, query)),
+        ".profile" => Some((QueryMode::Profile, query)),
+        _ => None,
+    }
+}
+
+fn validate_sparql_string(
+    input: &str,
+    base: Option<&str>,
+    prefixes: &str,
+) -> Result<ValidationResult, ReadlineError> {
+    if input.trim() == QUIT {
+        return Ok(ValidationResult::Valid(None));
+    }
+    let input = split_command(input).map_or(input, |(_, query)| query);
+    let input = format!("{prefixes}\n{input}");
+    let base = base.or(Some(RELATIVE_BASE));
+    // an update is as complete as a query
+    let query = Query::parse(&input, base)
+        .map(|_| ())
+        .or_else(|_| Update::parse(&input, base).map(|_| ()));
     //println!("\nline 42: {:?}", &query);
     // The following if needs to be removed in order for the
     // implementation to work
@@ -65,37 +117,124 @@ fn validate_sparql_string(input: &str) -> Result<ValidationResult, ReadlineError
 //
 // }
 
-///
-/// Read the function from the command line
-/// This function reads a sparql file from a command prompt
-/// TODO: Update the rl editor to handle syntax highlighting and multi line commands
-pub fn readlinefn(ns_dict: &Prefix) -> Option<String> {
-    // matching the
-    let helper = InputValidator {
-        //brackets: MatchingBracketValidator::new(),
-        validator: SparqlValidator::new(),
-        // highlighter: MatchingBracketHighlighter::new(),
+/// File of the query history of a session, under $XDG_DATA_HOME/sparqlite/history or
+/// ~/.local/share/sparqlite/history. Each saved database has its own history, the
+/// sessions with an in memory store share one
+pub fn history_path(db: Option<&str>) -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|data_home| !data_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(history_file(&data_home, db))
+}
+
+/// The history of a database is named after the database, with a hash of its path so
+/// two databases with the same name don't share it
+fn history_file(data_home: &Path, db: Option<&str>) -> PathBuf {
+    let dir = data_home.join("sparqlite").join("history");
+    let Some(db) = db else {
+        return dir.join("memory");
     };
+    let path = fs::canonicalize(db).unwrap_or_else(|_| PathBuf::from(db));
+    let name: String = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let hash = hex_digest(&Sha256::digest(path.to_string_lossy().as_bytes()));
+    dir.join(format!("{}-{}", name, &hash[..16]))
+}
+
+/// An interactive session. The editor is kept from one query to the next, with the
+/// queries typed before. A multi-line query is one entry of the history, so Ctrl-R
+/// finds and brings back the whole query
+pub struct Repl {
+    editor: Editor<InputValidator, DefaultHistory>,
+    /// The history is loaded from this file and each query is appended to it
+    history: Option<PathBuf>,
+}
 
-    let new_editor = Editor::new();
+impl Repl {
+    pub fn new(store: &Store, base: Option<&str>, history: Option<PathBuf>) -> Option<Repl> {
+        let helper = InputValidator {
+            completer: SparqlCompleter::new(store.clone()),
+            validator: SparqlValidator::new(base.map(str::to_string)),
+        };
+        let config = Config::builder()
+            .max_history_size(HISTORY_SIZE)
+            .and_then(|config| config.history_ignore_dups(true))
+            .map(|config| config.build())
+            .unwrap_or_default();
+        let mut editor = match Editor::with_config(config) {
+            Ok(editor) => editor,
+            Err(e) => {
+                println!("Error in Creating the editor: {}", e);
+                return None;
+            }
+        };
+        editor.set_helper(Some(helper));
 
-    if new_editor.is_err() {
-        println!("Error in Creating the editor");
-        return None;
+        // the session goes on without a history when its file can't be used
+        let history = history.filter(|path| {
+            let created = path.parent().map_or(Ok(()), fs::create_dir_all);
+            let loaded = created.map_err(ReadlineError::from).and_then(|_| {
+                match editor.load_history(path) {
+                    Err(ReadlineError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    loaded => loaded,
+                }
+            });
+            if let Err(e) = &loaded {
+                println!("Error in loading the history {}: {}", path.display(), e);
+            }
+            loaded.is_ok()
+        });
+        Some(Repl { editor, history })
     }
-    let mut editor = new_editor.unwrap();
-    editor.set_helper(Some(helper));
 
-    // editor.set_helper(Some(helper));
+    /// Read the next query, the prompt shows the prefixes of the query. None ends the
+    /// session, on .quit or Ctrl-D. Ctrl-C drops the query being typed
+    pub fn read(&mut self, ns_dict: &Prefix) -> Option<String> {
+        let prefixes = ns_dict.format_for_query();
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.validator.prefixes = prefixes.clone();
+            helper.completer.update_prefixes(ns_dict);
+        }
+        loop {
+            match self.editor.readline(&prefixes) {
+                Ok(line) if line.trim() == QUIT => return None,
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => {
+                    self.add_history(&line);
+                    return Some(line);
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return None,
+                Err(e) => {
+                    println!("Error in Reading the Line: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
 
-    let prefixes = ns_dict.format_for_query();
+    /// The store changed, the IRIs offered by the completion are read again
+    pub fn data_changed(&self) {
+        if let Some(helper) = self.editor.helper() {
+            helper.completer.data_changed();
+        }
+    }
 
-    let readline = editor.readline(&prefixes);
-    match readline {
-        Ok(line) => return Some(line),
-        Err(_) => {
-            println!("Error in Reading the Line");
-            return None;
+    /// Append the query to the history file right away, so it is kept when the session
+    /// ends without .quit and the other sessions of the database don't lose theirs
+    fn add_history(&mut self, query: &str) {
+        let _ = self.editor.add_history_entry(query);
+        if let Some(path) = &self.history {
+            if let Err(e) = self.editor.append_history(path) {
+                println!("Error in saving the history {}: {}", path.display(), e);
+                self.history = None;
+            }
         }
     }
 }
@@ -108,7 +247,7 @@ mod tests {
     #[test]
     fn valid_validation() {
         let valid_query = "SELECT ?s ?p ?o { ?s ?p ?o . }";
-        let result = validate_sparql_string(valid_query);
+        let result = validate_sparql_string(valid_query, None, "");
         match result {
             Ok(ValidationResult::Valid(_res)) => {
                 assert!(true);
@@ -117,10 +256,71 @@ mod tests {
         }
     }
 
+    #[test]
+    fn relative_iri_validation() {
+        let relative_query = "SELECT ?o { <data.ttl#a> ?p ?o . }";
+        let result = validate_sparql_string(relative_query, None, "");
+        match result {
+            Ok(ValidationResult::Valid(_res)) => (),
+            _ => panic!("Not good"),
+        }
+    }
+
+    #[test]
+    fn update_validation() {
+        let update = "INSERT DATA { <https://example.com/a> <https://example.com/p> 1 . }";
+        let result = validate_sparql_string(update, None, "");
+        match result {
+            Ok(ValidationResult::Valid(_res)) => (),
+            _ => panic!("Not good"),
+        }
+    }
+
+    #[test]
+    fn command_validation() {
+        let explained = ".explain SELECT ?s { ?s ?p ?o . }";
+        match validate_sparql_string(explained, None, "") {
+            Ok(ValidationResult::Valid(_res)) => (),
+            _ => panic!("Not good"),
+        }
+        match split_command(explained) {
+            Some((QueryMode::Explain, query)) => assert_eq!(query, "SELECT ?s { ?s ?p ?o . }"),
+            _ => panic!("Not good"),
+        }
+        assert!(split_command("SELECT ?s { ?s ?p ?o . }").is_none());
+    }
+
+    #[test]
+    fn prefixed_validation() {
+        let mut ns_dict = Prefix::new();
+        ns_dict.add(b"https://example.com/", b"ex");
+        let query = "SELECT ?s { ?s a ex:Person . }";
+        match validate_sparql_string(query, None, &ns_dict.format_for_query()) {
+            Ok(ValidationResult::Valid(_res)) => (),
+            _ => panic!("Not good"),
+        }
+        match validate_sparql_string(" .quit ", None, "") {
+            Ok(ValidationResult::Valid(_res)) => (),
+            _ => panic!("Not good"),
+        }
+    }
+
+    #[test]
+    fn should_key_history_by_database() {
+        let data_home = Path::new("/data");
+        let memory = history_file(data_home, None);
+        assert_eq!(memory, Path::new("/data/sparqlite/history/memory"));
+        let first = history_file(data_home, Some("/one/my db"));
+        let second = history_file(data_home, Some("/two/my db"));
+        assert_ne!(first, second);
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with("my_db-"));
+        assert_eq!(first, history_file(data_home, Some("/one/my db")));
+    }
+
     #[test]
     fn invalid_validation() {
         let incomplete_query = "SELECT ?s ?p ?o { ?s ?p. }";
-        let result = validate_sparql_string(incomplete_query);
+        let result = validate_sparql_string(incomplete_query, None, "");
         match result {
             Ok(ValidationResult::Incomplete) => assert!(true),
             _ => panic!("Not good"),
@@ -129,7 +329,7 @@ mod tests {
     #[test]
     fn second_invalid_validation() {
         let incomplete_query = "SELECT ?s ?p ?o ";
-        let result = validate_sparql_string(incomplete_query);
+        let result = validate_sparql_string(incomplete_query, None, "");
         match result {
             Ok(ValidationResult::Incomplete) => assert!(true),
             _ => panic!("Not good"),
@@ -0,0 +1,83 @@
+use oxigraph::io::RdfParseError;
+use oxigraph::store::LoaderError;
+use std::fmt;
+
+/// A problem found while loading a file, for the report printed after loading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadIssue {
+    pub file: String,
+    /// 1-based line of the problem, when the parser knows it
+    pub line: Option<u64>,
+    /// 1-based column of the problem, when the parser knows it
+    pub column: Option<u64>,
+    pub message: String,
+}
+
+impl LoadIssue {
+    pub fn new(file: &str, message: impl ToString) -> LoadIssue {
+        LoadIssue {
+            file: file.to_string(),
+            line: None,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+
+    /// The parsers count lines and columns from 0, the report counts them from 1
+    pub fn from_parse_error(file: &str, error: &RdfParseError) -> LoadIssue {
+        let location = match error {
+            RdfParseError::Syntax(e) => e.location(),
+            RdfParseError::Io(_) => None,
+        };
+        LoadIssue {
+            file: file.to_string(),
+            line: location.as_ref().map(|l| l.start.line + 1),
+            column: location.as_ref().map(|l| l.start.column + 1),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn from_loader_error(file: &str, error: &LoaderError) -> LoadIssue {
+        match error {
+            LoaderError::Parsing(e) => LoadIssue::from_parse_error(file, e),
+            _ => LoadIssue::new(file, error),
+        }
+    }
+}
+
+/// Same layout as compiler messages, `file:line:column: message`
+impl fmt::Display for LoadIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}", self.file, line, column, self.message)
+            }
+            _ => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use oxrdfio::{RdfFormat, RdfParser};
+
+    #[test]
+    fn should_report_syntax_error_location() {
+        let data = "<https://example.com/a> <https://example.com/p> \"a\" .\n<https://example.com/b> <https://example.com/p> .\n";
+        let error = RdfParser::from_format(RdfFormat::NTriples)
+            .for_reader(data.as_bytes())
+            .find_map(Result::err)
+            .unwrap();
+        let issue = LoadIssue::from_parse_error("data.nt", &error);
+        assert_eq!(issue.line, Some(2));
+        assert!(issue.to_string().starts_with("data.nt:2:"));
+    }
+
+    #[test]
+    fn should_report_issue_without_location() {
+        let issue = LoadIssue::new("data.ttl", "permission denied");
+        assert_eq!(issue.to_string(), "data.ttl: permission denied");
+    }
+}
@@ -0,0 +1,386 @@
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{
+    GraphName, IriParseError, Literal, NamedNode, NamedNodeRef, Quad, Subject, Term,
+};
+use oxigraph::store::{StorageError, Store};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Namespace of the terms sparqlite uses to describe the loaded files
+pub const SPARQLITE_NS: &str = "https://sparqlite.github.io/ns#";
+
+/// Metadata about a source file that was loaded into its own named graph
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// xsd:dateTime of the last modification, compared as a string between runs
+    pub modified: Option<String>,
+    /// None for a file that could not be read to the end, it is loaded again next time
+    pub sha256: Option<String>,
+    /// Named graphs the quads of a TriG or N-Quads file were loaded into, besides the
+    /// graph of the file. They are cleared with it when the file changes or is deleted
+    pub graphs: Vec<NamedNode>,
+}
+
+impl SourceFile {
+    /// Describe a file on disk, the hash is computed on the file as stored (still
+    /// compressed) while streaming through it
+    pub fn read(path: &Path) -> io::Result<SourceFile> {
+        let metadata = fs::metadata(path)?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        Ok(SourceFile {
+            path: std::path::absolute(path)?,
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(format_date_time),
+            sha256: Some(hex_digest(&hasher.finalize())),
+            graphs: Vec::new(),
+        })
+    }
+
+    /// Read back the description of `graph` saved by an earlier run
+    pub fn from_store(store: &Store, graph: &NamedNode) -> Result<Option<SourceFile>, StorageError> {
+        let value = |property: &str| -> Result<Option<String>, StorageError> {
+            let quad = store
+                .quads_for_pattern(
+                    Some(graph.as_ref().into()),
+                    Some(sparqlite_term(property).as_ref()),
+                    None,
+                    Some(GraphName::DefaultGraph.as_ref()),
+                )
+                .next()
+                .transpose()?;
+            Ok(quad.and_then(|quad| match quad.object {
+                Term::Literal(literal) => Some(literal.value().to_string()),
+                _ => None,
+            }))
+        };
+        let Some(path) = value("path")? else {
+            return Ok(None);
+        };
+        let mut graphs = Vec::new();
+        let quads = store.quads_for_pattern(
+            Some(graph.as_ref().into()),
+            Some(sparqlite_term("graph").as_ref()),
+            None,
+            Some(GraphName::DefaultGraph.as_ref()),
+        );
+        for quad in quads {
+            if let Term::NamedNode(named_graph) = quad?.object {
+                graphs.push(named_graph);
+            }
+        }
+        Ok(Some(SourceFile {
+            path: PathBuf::from(path),
+            size: value("size")?.and_then(|size| size.parse().ok()).unwrap_or_default(),
+            modified: value("modified")?,
+            sha256: value("sha256")?,
+            graphs,
+        }))
+    }
+
+    /// Cheap check on the size and modification time, without reading the file
+    pub fn is_stale(&self, path: &Path) -> bool {
+        if self.sha256.is_none() {
+            return true;
+        }
+        match fs::metadata(path) {
+            Ok(metadata) => {
+                metadata.len() != self.size
+                    || metadata.modified().ok().map(format_date_time) != self.modified
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Replace the description of `graph` in the default graph of the store
+    ///
+    /// ```turtle
+    /// <graph> a sparqlite:SourceFile ;
+    ///     sparqlite:path "/abs/path/file.ttl" ;
+    ///     sparqlite:size 1024 ;
+    ///     sparqlite:modified "2024-01-01T10:00:00Z"^^xsd:dateTime ;
+    ///     sparqlite:sha256 "..." ;
+    ///     sparqlite:graph <named-graph> .
+    /// ```
+    pub fn save_to_store(&self, store: &Store, graph: &NamedNode) -> Result<(), StorageError> {
+        remove_source(store, graph.as_ref())?;
+
+        let mut quads = vec![
+            source_quad(graph, "path", Literal::new_simple_literal(self.path.to_string_lossy())),
+            source_quad(graph, "size", Literal::new_typed_literal(self.size.to_string(), xsd::INTEGER)),
+            Quad::new(
+                graph.clone(),
+                rdf::TYPE,
+                sparqlite_term("SourceFile"),
+                GraphName::DefaultGraph,
+            ),
+        ];
+        if let Some(sha256) = &self.sha256 {
+            quads.push(source_quad(graph, "sha256", Literal::new_simple_literal(sha256)));
+        }
+        if let Some(modified) = &self.modified {
+            let date_time = Literal::new_typed_literal(modified, xsd::DATE_TIME);
+            quads.push(source_quad(graph, "modified", date_time));
+        }
+        for named_graph in &self.graphs {
+            quads.push(Quad::new(
+                graph.clone(),
+                sparqlite_term("graph"),
+                named_graph.clone(),
+                GraphName::DefaultGraph,
+            ));
+        }
+        for quad in &quads {
+            store.insert(quad)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove every triple describing `graph` from the default graph
+pub fn remove_source(store: &Store, graph: NamedNodeRef<'_>) -> Result<(), StorageError> {
+    let quads = store
+        .quads_for_pattern(Some(graph.into()), None, None, Some(GraphName::DefaultGraph.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    for quad in &quads {
+        store.remove(quad)?;
+    }
+    Ok(())
+}
+
+/// Every graph with a source file description, together with the path of the file
+pub fn stored_sources(store: &Store) -> Result<Vec<(NamedNode, PathBuf)>, StorageError> {
+    let mut sources = Vec::new();
+    let quads = store.quads_for_pattern(
+        None,
+        Some(sparqlite_term("path").as_ref()),
+        None,
+        Some(GraphName::DefaultGraph.as_ref()),
+    );
+    for quad in quads {
+        let quad = quad?;
+        if let (Subject::NamedNode(graph), Term::Literal(path)) = (quad.subject, quad.object) {
+            sources.push((graph, PathBuf::from(path.value())));
+        }
+    }
+    Ok(sources)
+}
+
+/// The graphs of the other files that also loaded quads into `named_graph`
+pub fn graph_writers(
+    store: &Store,
+    named_graph: &NamedNode,
+    graph: &NamedNode,
+) -> Result<Vec<NamedNode>, StorageError> {
+    let mut writers = Vec::new();
+    let quads = store.quads_for_pattern(
+        None,
+        Some(sparqlite_term("graph").as_ref()),
+        Some(named_graph.as_ref().into()),
+        Some(GraphName::DefaultGraph.as_ref()),
+    );
+    for quad in quads {
+        if let Subject::NamedNode(writer) = quad?.subject {
+            if writer != *graph {
+                writers.push(writer);
+            }
+        }
+    }
+    Ok(writers)
+}
+
+pub fn sparqlite_term(local_name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{SPARQLITE_NS}{local_name}"))
+}
+
+fn source_quad(graph: &NamedNode, property: &str, value: Literal) -> Quad {
+    Quad::new(
+        graph.clone(),
+        sparqlite_term(property),
+        value,
+        GraphName::DefaultGraph,
+    )
+}
+
+/// Name of the graph a file is loaded into.
+/// Without a template this is the `file://` IRI of the file, a template can use
+/// `{path}` (path relative to the data directory), `{name}` (file name) and `{stem}`
+/// (file name without extension), e.g. `https://example.com/graph/{path}`
+pub fn graph_name(path: &Path, root: &Path, template: Option<&str>) -> Result<NamedNode, IriParseError> {
+    let template = match template {
+        Some(template) => template,
+        None => {
+            let absolute = std::path::absolute(path).unwrap_or(path.to_path_buf());
+            return NamedNode::new(file_iri(&absolute));
+        }
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let iri = template
+        .replace("{path}", &percent_encode(&relative))
+        .replace("{name}", &percent_encode(&name))
+        .replace("{stem}", &percent_encode(&stem));
+    NamedNode::new(iri)
+}
+
+/// `file://` IRI of an absolute path
+pub fn file_iri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{}", percent_encode(&path))
+    } else {
+        format!("file:///{}", percent_encode(&path))
+    }
+}
+
+/// Default base IRI of the relative IRIs in a data or query file, the `file://` IRI of
+/// the file. A directory gets a trailing slash so relative IRIs resolve inside it
+pub fn base_iri(path: &Path) -> String {
+    let absolute = std::path::absolute(path).unwrap_or(path.to_path_buf());
+    let iri = file_iri(&absolute);
+    if absolute.is_dir() && !iri.ends_with('/') {
+        format!("{iri}/")
+    } else {
+        iri
+    }
+}
+
+/// Value parser for the `--base` argument, the base has to be an absolute IRI
+pub fn parse_base_arg(value: &str) -> Result<String, String> {
+    NamedNode::new(value)
+        .map(NamedNode::into_string)
+        .map_err(|e| format!("invalid base IRI '{value}': {e}"))
+}
+
+/// Percent encode everything but the unreserved IRI characters and the path separator
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+pub fn hex_digest(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// xsd:dateTime lexical form of a system time, in UTC
+fn format_date_time(time: SystemTime) -> String {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = duration.as_secs() as i64;
+    let (days, day_seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // civil date from the number of days since 1970-01-01
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let nanos = duration.subsec_nanos();
+    let fraction = if nanos == 0 {
+        String::new()
+    } else {
+        format!(".{nanos:09}").trim_end_matches('0').to_string()
+    };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}{fraction}Z",
+        day_seconds / 3600,
+        day_seconds % 3600 / 60,
+        day_seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn should_derive_base_from_path() {
+        let dir = std::env::temp_dir();
+        let base = base_iri(&dir);
+        assert!(base.starts_with("file:///"));
+        assert!(base.ends_with('/'));
+        assert!(base_iri(&dir.join("data.ttl")).ends_with("/data.ttl"));
+
+        assert!(parse_base_arg("https://example.com/data/").is_ok());
+        assert!(parse_base_arg("data/").is_err());
+    }
+
+    #[test]
+    fn should_build_graph_name_from_template() {
+        let path = Path::new("/data/onto/core model.ttl");
+        let graph = graph_name(path, Path::new("/data"), Some("https://example.com/graph/{path}")).unwrap();
+        assert_eq!(graph.as_str(), "https://example.com/graph/onto/core%20model.ttl");
+        let graph = graph_name(path, Path::new("/data"), Some("https://example.com/{stem}")).unwrap();
+        assert_eq!(graph.as_str(), "https://example.com/core%20model");
+    }
+
+    #[test]
+    fn should_read_back_loaded_source() {
+        use crate::load::tests::fixture;
+        use crate::load::{update_store, LoadStatus};
+        use crate::prefix::Prefix;
+        use std::collections::HashSet;
+
+        let (root, options) = fixture("source");
+        let file = root.join("data.trig");
+        let data = "<https://example.com/a> <https://example.com/p> 1 .\n\
+            GRAPH <https://example.com/g> { <https://example.com/a> <https://example.com/p> 2 }\n";
+        fs::write(&file, data).unwrap();
+
+        let mut store = Store::new().unwrap();
+        let mut loaded = HashSet::new();
+        let status = update_store(&mut store, &file, &root, &mut Prefix::new(), &options, &mut loaded, &mut Vec::new());
+        assert_eq!(status, Some(LoadStatus::Loaded));
+
+        let graph = graph_name(&file, &root, None).unwrap();
+        assert!(loaded.contains(&graph));
+        let source = SourceFile::from_store(&store, &graph).unwrap().unwrap();
+        let on_disk = SourceFile::read(&file).unwrap();
+        assert_eq!(source.path, std::path::absolute(&file).unwrap());
+        assert_eq!(source.size, data.len() as u64);
+        assert_eq!(source.modified, on_disk.modified);
+        assert!(source.modified.is_some());
+        assert_eq!(source.sha256, Some(hex_digest(&Sha256::digest(data))));
+        assert_eq!(source.graphs, [NamedNode::new("https://example.com/g").unwrap()]);
+        assert!(!source.is_stale(&file));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_build_file_iri() {
+        assert_eq!(file_iri(Path::new("/data/a b.ttl")), "file:///data/a%20b.ttl");
+    }
+
+    #[test]
+    fn should_format_date_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_294_400_250);
+        assert_eq!(format_date_time(time), "2024-03-01T12:00:00.25Z");
+        assert_eq!(format_date_time(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    }
+}
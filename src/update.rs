@@ -0,0 +1,156 @@
+use oxigraph::model::{BlankNode, GraphName, NamedNode, Quad, Subject, Term};
+use oxigraph::store::Store;
+use spargebra::algebra::GraphTarget;
+use spargebra::{GraphUpdateOperation, Update};
+use std::collections::{HashMap, HashSet};
+
+/// The quads an update adds to and removes from the store, relative to the store
+/// before the update
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    added: HashSet<Quad>,
+    removed: HashSet<Quad>,
+    /// Quads dropped by a CLEAR or DROP, which are only counted
+    cleared: usize,
+}
+
+impl Changes {
+    pub fn added(&self) -> usize {
+        self.added.len()
+    }
+
+    pub fn removed(&self) -> usize {
+        self.removed.len() + self.cleared
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.cleared == 0
+    }
+
+    fn contains(&self, store: &Store, quad: &Quad) -> bool {
+        self.added.contains(quad)
+            || (!self.removed.contains(quad) && store.contains(quad).unwrap_or_default())
+    }
+
+    fn insert(&mut self, store: &Store, quad: Quad) {
+        if !self.removed.remove(&quad) && !self.contains(store, &quad) {
+            self.added.insert(quad);
+        }
+    }
+
+    fn delete(&mut self, store: &Store, quad: Quad) {
+        if !self.added.remove(&quad) && self.contains(store, &quad) {
+            self.removed.insert(quad);
+        }
+    }
+}
+
+/// Count what an update will change before it runs: the quads of INSERT DATA and
+/// DELETE DATA are looked up in the store, the quads of a cleared graph are counted.
+/// None when the update can't be counted this way: a DELETE/INSERT with a WHERE
+/// pattern, a LOAD, and a CLEAR after an operation that changed the store
+pub fn count_changes(store: &Store, update: &Update) -> Option<Changes> {
+    let mut changes = Changes::default();
+    for operation in &update.operations {
+        match operation {
+            GraphUpdateOperation::InsertData { data } => {
+                // the blank nodes of the data are new ones in the store
+                let mut blank_nodes = HashMap::new();
+                for quad in data {
+                    let quad = Quad::new(
+                        fresh_subject(&quad.subject, &mut blank_nodes),
+                        quad.predicate.clone(),
+                        fresh_term(&quad.object, &mut blank_nodes),
+                        graph_name(&quad.graph_name),
+                    );
+                    changes.insert(store, quad);
+                }
+            }
+            GraphUpdateOperation::DeleteData { data } => {
+                for quad in data {
+                    let quad = Quad::new(
+                        Subject::from(quad.subject.clone()),
+                        quad.predicate.clone(),
+                        Term::from(quad.object.clone()),
+                        graph_name(&quad.graph_name),
+                    );
+                    changes.delete(store, quad);
+                }
+            }
+            // counting the solutions of the pattern would run it a second time
+            GraphUpdateOperation::DeleteInsert { .. } => return None,
+            GraphUpdateOperation::Clear { graph, .. } | GraphUpdateOperation::Drop { graph, .. } => {
+                if !changes.is_empty() {
+                    return None;
+                }
+                let count = |graph: Option<&NamedNode>| {
+                    let graph_name = graph.map_or(GraphName::DefaultGraph, |graph| graph.clone().into());
+                    store.quads_for_pattern(None, None, None, Some(graph_name.as_ref())).count()
+                };
+                changes.cleared = match graph {
+                    GraphTarget::NamedNode(graph) => count(Some(graph)),
+                    GraphTarget::DefaultGraph => count(None),
+                    GraphTarget::NamedGraphs => store.len().ok()? - count(None),
+                    GraphTarget::AllGraphs => store.len().ok()?,
+                };
+            }
+            GraphUpdateOperation::Create { .. } => (),
+            GraphUpdateOperation::Load { .. } => return None,
+        }
+    }
+    Some(changes)
+}
+
+fn graph_name(graph_name: &spargebra::term::GraphName) -> GraphName {
+    match graph_name {
+        spargebra::term::GraphName::NamedNode(graph) => graph.clone().into(),
+        spargebra::term::GraphName::DefaultGraph => GraphName::DefaultGraph,
+    }
+}
+
+fn fresh_subject(subject: &Subject, blank_nodes: &mut HashMap<BlankNode, BlankNode>) -> Subject {
+    match subject {
+        Subject::BlankNode(node) => blank_nodes.entry(node.clone()).or_default().clone().into(),
+        subject => subject.clone(),
+    }
+}
+
+fn fresh_term(term: &Term, blank_nodes: &mut HashMap<BlankNode, BlankNode>) -> Term {
+    match term {
+        Term::BlankNode(node) => blank_nodes.entry(node.clone()).or_default().clone().into(),
+        term => term.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn changes(store: &Store, update: &str) -> Option<(usize, usize)> {
+        let update = Update::parse(update, None).unwrap();
+        let changes = count_changes(store, &update)?;
+        store.update(oxigraph::sparql::Update::from(update)).unwrap();
+        Some((changes.added(), changes.removed()))
+    }
+
+    #[test]
+    fn should_count_changes_of_updates() {
+        let store = Store::new().unwrap();
+        let data = "PREFIX ex: <https://example.com/> INSERT DATA { ex:a ex:age 1 . ex:b ex:age 2 . [] ex:age 3 }";
+        assert_eq!(changes(&store, data), Some((3, 0)));
+        // the quads already in the store are not added again, unlike the blank node
+        assert_eq!(changes(&store, data), Some((1, 0)));
+
+        let update = "PREFIX ex: <https://example.com/>
+            DELETE { ?s ex:age ?age } INSERT { ?s ex:age ?next } WHERE { ?s ex:age ?age BIND(?age + 1 AS ?next) }";
+        assert_eq!(changes(&store, update), None);
+        // only the quads in the store are removed
+        let update = "PREFIX ex: <https://example.com/> DELETE DATA { ex:a ex:age 1 . ex:c ex:age 1 }";
+        assert_eq!(changes(&store, update), Some((0, 1)));
+
+        let update = "PREFIX ex: <https://example.com/> DELETE DATA { ex:b ex:age 2 } ; CLEAR DEFAULT";
+        assert_eq!(changes(&store, update), None);
+        assert_eq!(changes(&store, "CLEAR DEFAULT"), Some((0, 3)));
+    }
+}
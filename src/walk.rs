@@ -0,0 +1,149 @@
+use clap::ValueEnum;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::format::format_from_path;
+
+/// How symbolic links found while walking a data directory are treated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Symlinks {
+    /// Follow links to files and directories (loops are detected and skipped)
+    Follow,
+    /// Ignore every symbolic link
+    Skip,
+}
+
+pub struct WalkOptions {
+    pub include: GlobSet,
+    pub exclude: GlobSet,
+    pub symlinks: Symlinks,
+    /// When a format is forced every file is a candidate, otherwise only the
+    /// files with a known RDF extension are picked up
+    pub any_extension: bool,
+}
+
+impl WalkOptions {
+    pub fn new(
+        include: &[Glob],
+        exclude: &[Glob],
+        symlinks: Symlinks,
+        any_extension: bool,
+    ) -> Result<WalkOptions, globset::Error> {
+        Ok(WalkOptions {
+            include: build_globset(include)?,
+            exclude: build_globset(exclude)?,
+            symlinks,
+            any_extension,
+        })
+    }
+
+    /// Include and exclude patterns are matched against the path relative to the data
+    /// directory, on top of the check on the extension
+    fn is_selected(&self, relative: &Path) -> bool {
+        if self.exclude.is_match(relative) {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.is_match(relative) {
+            return false;
+        }
+        self.any_extension || format_from_path(relative).is_some()
+    }
+}
+
+fn build_globset(globs: &[Glob]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    builder.build()
+}
+
+/// Value parser for the `--include` and `--exclude` arguments
+pub fn parse_glob_arg(pattern: &str) -> Result<Glob, String> {
+    Glob::new(pattern).map_err(|e| e.to_string())
+}
+
+/// Recursively collect the data files below `root`.
+/// Entries are visited in file name order so the load order is the same on every run
+pub fn collect_files(root: &Path, options: &WalkOptions) -> Vec<PathBuf> {
+    let walker = WalkDir::new(root)
+        .follow_links(options.symlinks == Symlinks::Follow)
+        .sort_by_file_name();
+
+    let mut files = Vec::new();
+    let entries = walker.into_iter().filter_entry(|entry| {
+        // the root itself is always walked, pruning happens on the excluded sub directories
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        entry.depth() == 0 || !options.exclude.is_match(relative)
+    });
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                println!("Path contains error: {}", e);
+                continue;
+            }
+        };
+        if entry.path_is_symlink() && options.symlinks == Symlinks::Skip {
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        if options.is_selected(relative) {
+            files.push(entry.into_path());
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+
+    fn data_dir(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("sparqlite-walk-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b/nested")).unwrap();
+        fs::create_dir_all(root.join("a")).unwrap();
+        for file in ["z.ttl", "README.md", "a/one.nt", "b/two.trig", "b/nested/three.ttl", "b/data.csv"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        root
+    }
+
+    fn relative_names(root: &Path, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn should_walk_recursively_in_name_order() {
+        let root = data_dir("order");
+        let options = WalkOptions::new(&[], &[], Symlinks::Follow, false).unwrap();
+        let files = relative_names(&root, collect_files(&root, &options));
+        assert_eq!(files, vec!["a/one.nt", "b/nested/three.ttl", "b/two.trig", "z.ttl"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_apply_include_and_exclude_globs() {
+        let root = data_dir("globs");
+        let include = [parse_glob_arg("*.ttl").unwrap(), parse_glob_arg("*.csv").unwrap()];
+        let exclude = [parse_glob_arg("b/nested").unwrap()];
+        let options = WalkOptions::new(&include, &exclude, Symlinks::Follow, false).unwrap();
+        let files = relative_names(&root, collect_files(&root, &options));
+        // an included file still needs an RDF extension, unless the format is given
+        assert_eq!(files, vec!["z.ttl"]);
+        let options = WalkOptions::new(&include, &exclude, Symlinks::Follow, true).unwrap();
+        let files = relative_names(&root, collect_files(&root, &options));
+        assert_eq!(files, vec!["b/data.csv", "z.ttl"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
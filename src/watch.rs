@@ -0,0 +1,40 @@
+use oxigraph::store::Store;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::load::{load_data, LoadOptions, LoadSummary};
+use crate::prefix::Prefix;
+use crate::walk::WalkOptions;
+
+/// How often the data paths are scanned for created, modified and deleted files.
+/// Polling works the same on every platform and file system (network mounts
+/// included) without another dependency, at the cost of a stat of every file per pass
+/// and up to a second before a change is picked up
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keep the store in sync with the data paths from a background thread.
+/// The files are loaded per graph, so every pass only reloads what changed since the
+/// previous one, a file that failed is only tried again once it changed. The summary
+/// of each pass that changed the store or found problems is sent on the returned
+/// channel, the prefixes found in the new files are saved to the store
+pub fn watch(
+    mut store: Store,
+    data: Vec<String>,
+    walk_options: WalkOptions,
+    load_options: LoadOptions,
+) -> Receiver<LoadSummary> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let mut ns_dict = Prefix::new();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let summary = load_data(&mut store, &data, &walk_options, &load_options, &mut ns_dict);
+            // the session is over when nobody listens to the changes anymore
+            if (summary.has_changes() || !summary.issues.is_empty()) && sender.send(summary).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}